@@ -5,7 +5,7 @@
  * They delegate to the actual context implementations in the core app.
  */
 
-import { useCallback, useContext, useEffect, useState } from 'react';
+import { useCallback, useContext, useEffect, useMemo, useState } from 'react';
 import { resolveAgentName } from './agentResolver';
 import { _getApiBase, _getPluginName } from './api';
 import { SDKContext } from './providers';
@@ -450,6 +450,34 @@ export function useServerFetch() {
   );
 }
 
+/**
+ * Secret storage for plugins, kept in the desktop app's keyring.
+ * Requires `secrets.store` permission in plugin.json. Secrets are scoped to
+ * the calling plugin, so keys never clash with or reveal another plugin's.
+ */
+export function useSecrets() {
+  return useMemo(() => {
+    const call = async <T>(command: string, args: Record<string, unknown>) => {
+      const plugin = _getPluginName();
+      if (!plugin) throw new Error('Secrets are only available to plugins');
+      if (typeof window === 'undefined' || !('__TAURI__' in window)) {
+        throw new Error('Secrets are only available in the desktop app');
+      }
+      const { invoke } = await import('@tauri-apps/api/core');
+      return invoke<T>(command, { plugin, ...args });
+    };
+    return {
+      get: (key: string) => call<string | null>('secret_get', { key }),
+      set: (key: string, value: string) =>
+        call<void>('secret_set', { key, value }),
+      /** Resolves false when there was no such secret. */
+      delete: (key: string) => call<boolean>('secret_delete', { key }),
+      /** Keys only; values are read one at a time with `get`. */
+      list: () => call<string[]>('secret_list', {}),
+    };
+  }, []);
+}
+
 // Knowledge Management (convenience wrappers around query hooks)
 export function useKnowledgeNamespaces(projectSlug: string) {
   return useKnowledgeNamespacesQuery(projectSlug);
@@ -100,6 +100,8 @@ export {
   useResolveAgent,
   // SDK access
   useSDK,
+  // Secret storage
+  useSecrets,
   useSendMessage,
   // Chat utilities
   useSendToChat,
@@ -0,0 +1,57 @@
+#!/usr/bin/env bash
+# Download the official Node.js binary for the host (or $TARGET_TRIPLE) into
+# src-desktop/binaries/node-<triple>, where Tauri's externalBin expects it.
+# The version defaults to the newest release of the line pinned in .nvmrc.
+# Usage: ./scripts/fetch-node-sidecar.sh [version]
+set -euo pipefail
+
+ROOT="$(cd "$(dirname "$0")/.." && pwd)"
+OUT_DIR="$ROOT/src-desktop/binaries"
+TRIPLE="${TARGET_TRIPLE:-$(rustc -vV | sed -n 's/^host: //p')}"
+LINE="$(tr -d '[:space:]v' < "$ROOT/.nvmrc")"
+VERSION="${1:-${NODE_SIDECAR_VERSION:-}}"
+
+if [ -z "$VERSION" ]; then
+  VERSION="$(curl -fsSL https://nodejs.org/dist/index.json \
+    | grep -o "\"version\":\"v${LINE}\.[0-9.]*\"" | head -1 | cut -d'"' -f4)"
+fi
+VERSION="v${VERSION#v}"
+
+case "$TRIPLE" in
+  aarch64-apple-darwin)      PLATFORM=darwin-arm64 ;;
+  x86_64-apple-darwin)       PLATFORM=darwin-x64 ;;
+  x86_64-unknown-linux-gnu)  PLATFORM=linux-x64 ;;
+  aarch64-unknown-linux-gnu) PLATFORM=linux-arm64 ;;
+  x86_64-pc-windows-msvc)    PLATFORM=win-x64 ;;
+  *) echo "Unsupported target: $TRIPLE" >&2; exit 1 ;;
+esac
+
+DEST="$OUT_DIR/node-$TRIPLE"
+[ "$PLATFORM" = win-x64 ] && DEST="$DEST.exe"
+if [ -x "$DEST" ] && [ "$("$DEST" --version 2>/dev/null || true)" = "$VERSION" ]; then
+  echo "Node $VERSION already at $DEST"
+  exit 0
+fi
+
+TMP="$(mktemp -d)"
+trap 'rm -rf "$TMP"' EXIT
+BASE="https://nodejs.org/dist/$VERSION"
+NAME="node-$VERSION-$PLATFORM"
+ARCHIVE="$NAME.tar.gz"
+[ "$PLATFORM" = win-x64 ] && ARCHIVE="$NAME.zip"
+
+echo "=== Downloading Node $VERSION for $TRIPLE ==="
+curl -fsSL "$BASE/$ARCHIVE" -o "$TMP/$ARCHIVE"
+curl -fsSL "$BASE/SHASUMS256.txt" -o "$TMP/SHASUMS256.txt"
+(cd "$TMP" && grep " $ARCHIVE\$" SHASUMS256.txt | shasum -a 256 -c -)
+
+mkdir -p "$OUT_DIR"
+if [ "$PLATFORM" = win-x64 ]; then
+  unzip -q "$TMP/$ARCHIVE" "$NAME/node.exe" -d "$TMP"
+  cp "$TMP/$NAME/node.exe" "$DEST"
+else
+  tar -xzf "$TMP/$ARCHIVE" -C "$TMP" "$NAME/bin/node"
+  cp "$TMP/$NAME/bin/node" "$DEST"
+  chmod +x "$DEST"
+fi
+echo "Node $VERSION -> $DEST"
@@ -0,0 +1,134 @@
+#!/usr/bin/env node
+// Mock OAuth 2.0 authorization server for trying `start_oauth_flow` without
+// a real provider. /authorize approves every request at once and redirects
+// back with a code; /token checks the code, redirect URI, client id and PKCE
+// verifier like a real server would before issuing tokens.
+//
+//   node scripts/mock-oauth-server.mjs
+//   PORT=9000 MOCK_OAUTH_DENY=1 node scripts/mock-oauth-server.mjs
+//
+// Point a flow at http://127.0.0.1:<port>/authorize and /token.
+
+import { createHash, randomBytes } from 'node:crypto';
+import { createServer } from 'node:http';
+
+const port = Number(process.env.PORT ?? 8765);
+const deny = process.env.MOCK_OAUTH_DENY === '1';
+const codes = new Map();
+
+function isLoopback(uri) {
+  try {
+    const { protocol, hostname } = new URL(uri);
+    return (
+      protocol === 'http:' &&
+      ['127.0.0.1', 'localhost', '[::1]'].includes(hostname)
+    );
+  } catch {
+    return false;
+  }
+}
+
+function json(res, status, body) {
+  res.writeHead(status, { 'Content-Type': 'application/json' });
+  res.end(JSON.stringify(body));
+}
+
+function authorizeProblem(q) {
+  if (q.get('response_type') !== 'code') return 'response_type must be code';
+  if (!q.get('client_id')) return 'client_id is required';
+  if (!isLoopback(q.get('redirect_uri'))) {
+    return 'redirect_uri must be a loopback URL';
+  }
+  if (q.get('code_challenge_method') !== 'S256' || !q.get('code_challenge')) {
+    return 'an S256 code_challenge is required';
+  }
+  return null;
+}
+
+function authorize(url, res) {
+  const q = url.searchParams;
+  const redirectUri = q.get('redirect_uri');
+  const problem = authorizeProblem(q);
+  if (problem) {
+    res.writeHead(400, { 'Content-Type': 'text/plain' });
+    res.end(`${problem}\n`);
+    return;
+  }
+
+  const back = new URL(redirectUri);
+  if (deny) {
+    back.searchParams.set('error', 'access_denied');
+    back.searchParams.set('error_description', 'Denied by the mock server');
+  } else {
+    const code = randomBytes(16).toString('hex');
+    codes.set(code, {
+      clientId: q.get('client_id'),
+      redirectUri,
+      challenge: q.get('code_challenge'),
+    });
+    back.searchParams.set('code', code);
+  }
+  if (q.has('state')) back.searchParams.set('state', q.get('state'));
+  console.log(`authorize ${q.get('client_id')} -> ${back}`);
+  res.writeHead(302, { Location: back.toString() });
+  res.end();
+}
+
+function tokenProblem(form, grant) {
+  if (form.get('grant_type') !== 'authorization_code') {
+    return 'unsupported grant_type';
+  }
+  if (!grant) return 'unknown or used code';
+  if (grant.clientId !== form.get('client_id')) {
+    return 'client_id does not match';
+  }
+  if (grant.redirectUri !== form.get('redirect_uri')) {
+    return 'redirect_uri does not match';
+  }
+  const verifier = form.get('code_verifier') ?? '';
+  const challenge = createHash('sha256').update(verifier).digest('base64url');
+  if (grant.challenge !== challenge) {
+    return 'code_verifier does not match the challenge';
+  }
+  return null;
+}
+
+function token(body, res) {
+  const form = new URLSearchParams(body);
+  const grant = codes.get(form.get('code'));
+  // Codes are single use, even when the exchange fails.
+  codes.delete(form.get('code'));
+  const problem = tokenProblem(form, grant);
+  if (problem) {
+    console.log(`token refused: ${problem}`);
+    json(res, 400, { error: 'invalid_grant', error_description: problem });
+    return;
+  }
+  console.log(`token issued to ${grant.clientId}`);
+  json(res, 200, {
+    access_token: `mock-access-${randomBytes(8).toString('hex')}`,
+    token_type: 'Bearer',
+    expires_in: 3600,
+    refresh_token: `mock-refresh-${randomBytes(8).toString('hex')}`,
+    scope: 'read',
+  });
+}
+
+createServer((req, res) => {
+  const url = new URL(req.url, `http://127.0.0.1:${port}`);
+  if (req.method === 'GET' && url.pathname === '/authorize') {
+    authorize(url, res);
+  } else if (req.method === 'POST' && url.pathname === '/token') {
+    let body = '';
+    req.on('data', (chunk) => {
+      body += chunk;
+    });
+    req.on('end', () => token(body, res));
+  } else {
+    json(res, 404, { error: 'not_found' });
+  }
+}).listen(port, '127.0.0.1', () => {
+  console.log(`Mock OAuth server on http://127.0.0.1:${port}`);
+  console.log(`  authorizeUrl: http://127.0.0.1:${port}/authorize`);
+  console.log(`  tokenUrl:     http://127.0.0.1:${port}/token`);
+});
@@ -1,3 +1,46 @@
+use std::path::Path;
+
+/// Used when neither `package.json` engines nor `.nvmrc` name a version.
+const DEFAULT_MIN_NODE: &str = "20";
+
 fn main() {
+    embed_min_node_version();
     tauri_build::build()
 }
+
+/// Exposes the project's minimum Node version to the shell as
+/// `STALLION_MIN_NODE`, read from `package.json` `engines.node` or, failing
+/// that, `.nvmrc`.
+fn embed_min_node_version() {
+    let root = Path::new("..");
+    let package_json = root.join("package.json");
+    let nvmrc = root.join(".nvmrc");
+    println!("cargo:rerun-if-changed={}", package_json.display());
+    println!("cargo:rerun-if-changed={}", nvmrc.display());
+
+    let from_engines = std::fs::read_to_string(&package_json)
+        .ok()
+        .and_then(|raw| serde_json::from_str::<serde_json::Value>(&raw).ok())
+        .and_then(|pkg| pkg["engines"]["node"].as_str().and_then(leading_version));
+    let from_nvmrc = || {
+        std::fs::read_to_string(&nvmrc)
+            .ok()
+            .and_then(|raw| leading_version(&raw))
+    };
+    let version = from_engines
+        .or_else(from_nvmrc)
+        .unwrap_or_else(|| DEFAULT_MIN_NODE.to_string());
+    println!("cargo:rustc-env=STALLION_MIN_NODE={version}");
+}
+
+/// The first version in a range or alias such as `>=20.11`, `^18.0.0 || >=20`
+/// or `v20`. Aliases like `lts/iron` have no version and yield `None`.
+fn leading_version(spec: &str) -> Option<String> {
+    let start = spec.find(|c: char| c.is_ascii_digit())?;
+    let version: String = spec[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let version = version.trim_end_matches('.');
+    (!version.is_empty()).then(|| version.to_string())
+}
@@ -0,0 +1,808 @@
+//! External authentication commands.
+//!
+//! Providers are configured by id under `auth.providers` in `desktop.json`,
+//! so AWS SSO, a PIN flow and a token refresh can live side by side; the
+//! legacy `AUTH_COMMAND` variable shows up as the `default` provider. A
+//! provider's command is run directly from its argv, never through a shell,
+//! with the login shell environment.
+//! The PIN is written to its stdin, so nothing the user types can change
+//! what runs. The command runs in its own process group and is killed with
+//! everything it started on timeout or cancellation.
+//!
+//! Success is decided by the configured check (exit code, a JSON value or a
+//! regex on stdout), and failures are typed so the UI can tell a rejected
+//! PIN from a missing binary or a timeout. The time of each provider's last
+//! success is kept in `.auth-history.json` for the provider list.
+//!
+//! Output streams to the UI line by line on `auth://output` while the command
+//! runs, so device-code flows can show their code. URLs and one-time codes
+//! found in it are also sent on `auth://prompt`, and the first URL can be
+//! opened in the browser. Interactive providers keep stdin open for answers
+//! sent with `send_auth_input`.
+
+use crate::error::{serialize_command_error, CommandError};
+use crate::logs::Stream;
+use crate::node::is_executable;
+use crate::procgroup;
+use crate::settings::{AuthProvider, AuthSettings, DesktopSettings, SuccessCheck};
+use crate::shellenv::ShellEnvResolver;
+use chrono::{SecondsFormat, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+use tauri_plugin_opener::OpenerExt;
+
+pub const OUTPUT_EVENT: &str = "auth://output";
+pub const PROMPT_EVENT: &str = "auth://prompt";
+
+/// Run through `sh -c` as the `default` provider, for compatibility. The PIN
+/// still only reaches it on stdin.
+const LEGACY_VAR: &str = "AUTH_COMMAND";
+const LEGACY_ID: &str = "default";
+const HISTORY_FILE: &str = ".auth-history.json";
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long an unfinished line waits for the rest before the UI is shown it.
+const PARTIAL_AFTER: Duration = Duration::from_millis(200);
+/// Output lines quoted in error messages.
+const ERROR_LINES: usize = 20;
+
+static URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"https?://[^\s<>"'`]+"#).unwrap());
+/// Device codes such as `ABCD-EFGH` or `1A2B-3C4D`.
+static CODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b[A-Z0-9]{4,}(?:-[A-Z0-9]{4,})+\b").unwrap());
+
+#[derive(Debug)]
+pub enum AuthError {
+    UnknownProvider { id: String },
+    /// The provider reads a PIN and none was given.
+    PinRequired,
+    /// The program isn't installed or isn't on the login `PATH`.
+    MissingBinary { program: String },
+    Timeout { after: Duration },
+    Cancelled,
+    /// Another authentication is still running.
+    Busy,
+    /// Input was sent to a provider that isn't authenticating.
+    NotRunning { id: String },
+    BadPin { output: String },
+    /// The command ran but didn't pass the success check.
+    Failed { status: Option<i32>, output: String },
+    /// The `auth` settings can't be used as written.
+    InvalidConfig(String),
+    Io(String),
+}
+
+impl CommandError for AuthError {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::UnknownProvider { .. } => "unknownProvider",
+            Self::PinRequired => "pinRequired",
+            Self::MissingBinary { .. } => "missingBinary",
+            Self::Timeout { .. } => "timeout",
+            Self::Cancelled => "cancelled",
+            Self::Busy => "busy",
+            Self::NotRunning { .. } => "notRunning",
+            Self::BadPin { .. } => "badPin",
+            Self::Failed { .. } => "failed",
+            Self::InvalidConfig(_) => "invalidConfig",
+            Self::Io(_) => "io",
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownProvider { id } => write!(
+                f,
+                "No auth provider named {id:?}. Add it to auth.providers in ~/.stallion-ai/desktop.json."
+            ),
+            Self::PinRequired => write!(f, "This provider needs a PIN"),
+            Self::MissingBinary { program } => {
+                write!(f, "{program} was not found. Is it installed and on your login shell's PATH?")
+            }
+            Self::Timeout { after } if after.as_secs() == 0 => {
+                write!(f, "Authentication timed out after {}ms", after.as_millis())
+            }
+            Self::Timeout { after } => {
+                write!(f, "Authentication timed out after {}s", after.as_secs())
+            }
+            Self::Cancelled => write!(f, "Authentication was cancelled"),
+            Self::Busy => write!(f, "Authentication is already in progress"),
+            Self::NotRunning { id } => write!(f, "{id} is not authenticating"),
+            Self::BadPin { output } => {
+                write!(f, "The PIN was rejected")?;
+                if !output.is_empty() {
+                    write!(f, ":\n{output}")?;
+                }
+                Ok(())
+            }
+            Self::Failed { status, output } => {
+                match status {
+                    Some(code) => write!(f, "Authentication failed (exit code {code})")?,
+                    None => write!(f, "Authentication failed")?,
+                }
+                if !output.is_empty() {
+                    write!(f, ":\n{output}")?;
+                }
+                Ok(())
+            }
+            Self::InvalidConfig(message) => write!(f, "Invalid auth provider: {message}"),
+            Self::Io(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+serialize_command_error!(AuthError);
+
+/// An authentication in progress.
+#[derive(Default)]
+struct Run {
+    cancel: AtomicBool,
+    /// The command's stdin while it's open for follow-up input.
+    stdin: Mutex<Option<ChildStdin>>,
+}
+
+/// The authentications in progress, by provider.
+#[derive(Default)]
+pub struct AuthRuns(Mutex<HashMap<String, Arc<Run>>>);
+
+impl AuthRuns {
+    fn begin(&self, id: &str) -> Result<RunGuard<'_>, AuthError> {
+        let mut running = self.0.lock().unwrap();
+        if running.contains_key(id) {
+            return Err(AuthError::Busy);
+        }
+        let run = Arc::new(Run::default());
+        running.insert(id.to_string(), run.clone());
+        Ok(RunGuard {
+            runs: self,
+            id: id.to_string(),
+            run,
+        })
+    }
+
+    fn get(&self, id: &str) -> Option<Arc<Run>> {
+        self.0.lock().unwrap().get(id).cloned()
+    }
+
+    fn cancel(&self, id: &str) -> bool {
+        match self.get(id) {
+            Some(run) => {
+                run.cancel.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn is_running(&self, id: &str) -> bool {
+        self.0.lock().unwrap().contains_key(id)
+    }
+
+    fn record_success(&self, data_dir: &Path, id: &str) {
+        // Held so providers finishing together don't overwrite each other.
+        let _running = self.0.lock().unwrap();
+        let mut history = load_history(data_dir);
+        history.entry(id.to_string()).or_default().last_success =
+            Some(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true));
+        let path = data_dir.join(HISTORY_FILE);
+        let result = serde_json::to_string_pretty(&history)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(&path, json + "\n").map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            eprintln!("Failed to write {}: {e}", path.display());
+        }
+    }
+}
+
+/// Clears the run when authentication ends, however it ends.
+struct RunGuard<'a> {
+    runs: &'a AuthRuns,
+    id: String,
+    run: Arc<Run>,
+}
+
+impl Drop for RunGuard<'_> {
+    fn drop(&mut self) {
+        self.runs.0.lock().unwrap().remove(&self.id);
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ProviderHistory {
+    last_success: Option<String>,
+}
+
+fn load_history(data_dir: &Path) -> BTreeMap<String, ProviderHistory> {
+    std::fs::read_to_string(data_dir.join(HISTORY_FILE))
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+/// A provider as listed to the UI.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderInfo {
+    id: String,
+    name: String,
+    program: Option<String>,
+    needs_pin: bool,
+    running: bool,
+    last_success: Option<String>,
+}
+
+/// The configured providers in order, plus `AUTH_COMMAND` as the `default`
+/// provider unless one is configured with that id. Providers without an id
+/// are skipped and the first of several with the same id wins.
+fn providers(settings: &AuthSettings) -> Vec<AuthProvider> {
+    let mut seen = HashSet::new();
+    let mut providers = Vec::new();
+    for provider in &settings.providers {
+        if provider.id.is_empty() {
+            eprintln!("Ignoring auth provider without an id");
+        } else if !seen.insert(provider.id.clone()) {
+            eprintln!("Ignoring duplicate auth provider {:?}", provider.id);
+        } else {
+            providers.push(provider.clone());
+        }
+    }
+    if let Ok(legacy) = std::env::var(LEGACY_VAR) {
+        if !seen.contains(LEGACY_ID) {
+            providers.push(AuthProvider {
+                id: LEGACY_ID.into(),
+                name: Some(LEGACY_VAR.into()),
+                command: vec!["sh".into(), "-c".into(), legacy],
+                ..AuthProvider::default()
+            });
+        }
+    }
+    providers
+}
+
+/// A line of output as the UI sees it.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OutputLine {
+    provider: String,
+    stream: Stream,
+    line: String,
+    /// Written without a newline, usually a prompt waiting for input.
+    partial: bool,
+}
+
+/// Something in the output the user has to act on.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Prompt {
+    provider: String,
+    url: Option<String>,
+    code: Option<String>,
+    /// Whether `url` was opened in the browser.
+    opened: bool,
+}
+
+/// Called from the reader threads with each line of output as it arrives.
+type LineSink = Arc<dyn Fn(Stream, &str, bool) + Send + Sync>;
+
+/// A configured command, validated and ready to run.
+struct Runner {
+    argv: Vec<String>,
+    timeout: Duration,
+    pin_stdin: bool,
+    interactive: bool,
+    success: Check,
+    bad_pin_exit_codes: Vec<i32>,
+    bad_pin_pattern: Option<Regex>,
+}
+
+enum Check {
+    ExitCode(Vec<i32>),
+    Json { pointer: String, equals: serde_json::Value },
+    Regex(Regex),
+}
+
+struct Output {
+    status: ExitStatus,
+    stdout: String,
+    stderr: String,
+}
+
+impl Runner {
+    fn from_provider(settings: &AuthProvider) -> Result<Self, AuthError> {
+        if settings.command.is_empty() {
+            return Err(AuthError::InvalidConfig(format!("{} has no command", settings.id)));
+        }
+        let compile = |pattern: &str| {
+            Regex::new(pattern).map_err(|e| AuthError::InvalidConfig(format!("bad pattern {pattern:?}: {e}")))
+        };
+        let success = match &settings.success {
+            SuccessCheck::ExitCode { codes } => Check::ExitCode(codes.clone()),
+            SuccessCheck::Json { pointer, equals } => {
+                if !pointer.is_empty() && !pointer.starts_with('/') {
+                    return Err(AuthError::InvalidConfig(format!(
+                        "JSON pointer {pointer:?} must start with '/'"
+                    )));
+                }
+                Check::Json {
+                    pointer: pointer.clone(),
+                    equals: equals.clone(),
+                }
+            }
+            SuccessCheck::Regex { pattern } => Check::Regex(compile(pattern)?),
+        };
+        Ok(Self {
+            argv: settings.command.clone(),
+            timeout: Duration::from_millis(settings.timeout_ms),
+            pin_stdin: settings.pin_stdin,
+            interactive: settings.interactive,
+            success,
+            bad_pin_exit_codes: settings.bad_pin_exit_codes.clone(),
+            bad_pin_pattern: settings.bad_pin_pattern.as_deref().map(compile).transpose()?,
+        })
+    }
+
+    fn run(
+        &self,
+        pin: Option<&str>,
+        env: &BTreeMap<String, String>,
+        run: &Run,
+        on_line: LineSink,
+    ) -> Result<String, AuthError> {
+        let output = self.execute(pin, env, run, on_line)?;
+        let combined = format!("{}{}", output.stdout, output.stderr);
+        let code = output.status.code();
+
+        let bad_pin = code.is_some_and(|c| self.bad_pin_exit_codes.contains(&c))
+            || self.bad_pin_pattern.as_ref().is_some_and(|re| re.is_match(&combined));
+        if bad_pin {
+            return Err(AuthError::BadPin {
+                output: last_lines(&combined),
+            });
+        }
+        if self.succeeded(&output) {
+            Ok(output.stdout)
+        } else {
+            Err(AuthError::Failed {
+                status: code,
+                output: last_lines(&combined),
+            })
+        }
+    }
+
+    fn succeeded(&self, output: &Output) -> bool {
+        match &self.success {
+            Check::ExitCode(codes) => output.status.code().is_some_and(|c| codes.contains(&c)),
+            Check::Regex(re) => re.is_match(&output.stdout),
+            Check::Json { pointer, equals } => {
+                let trimmed = output.stdout.trim();
+                // A whole JSON document, or a JSON result on the last line
+                // after progress output.
+                let value = serde_json::from_str::<serde_json::Value>(trimmed).ok().or_else(|| {
+                    let last = trimmed.lines().rev().find(|l| !l.trim().is_empty())?;
+                    serde_json::from_str(last).ok()
+                });
+                value.as_ref().and_then(|v| v.pointer(pointer)) == Some(equals)
+            }
+        }
+    }
+
+    fn execute(
+        &self,
+        pin: Option<&str>,
+        env: &BTreeMap<String, String>,
+        run: &Run,
+        on_line: LineSink,
+    ) -> Result<Output, AuthError> {
+        let program = &self.argv[0];
+        let resolved = find_program(program, env.get("PATH").map(String::as_str)).ok_or_else(|| {
+            AuthError::MissingBinary {
+                program: program.clone(),
+            }
+        })?;
+
+        let mut cmd = Command::new(&resolved);
+        cmd.args(&self.argv[1..])
+            .envs(env)
+            .stdin(if self.pin_stdin || self.interactive {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        procgroup::isolate(&mut cmd);
+        let mut child = cmd.spawn().map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => AuthError::MissingBinary {
+                program: program.clone(),
+            },
+            _ => AuthError::Io(format!("Failed to start {program}: {e}")),
+        })?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            if let Some(pin) = pin.filter(|_| self.pin_stdin) {
+                // A command that exits without reading its stdin is not an
+                // error here; its exit status says what happened.
+                let _ = stdin.write_all(format!("{pin}\n").as_bytes());
+            }
+            // Otherwise dropped here, so the command sees the end of input.
+            if self.interactive {
+                *run.stdin.lock().unwrap() = Some(stdin);
+            }
+        }
+        let sink = on_line.clone();
+        let stdout = collect(child.stdout.take(), move |line, partial| sink(Stream::Stdout, line, partial));
+        let stderr = collect(child.stderr.take(), move |line, partial| on_line(Stream::Stderr, line, partial));
+
+        let start = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if run.cancel.load(Ordering::SeqCst) => {
+                    procgroup::kill_and_reap(&mut child);
+                    return Err(AuthError::Cancelled);
+                }
+                Ok(None) if start.elapsed() >= self.timeout => {
+                    procgroup::kill_and_reap(&mut child);
+                    return Err(AuthError::Timeout { after: self.timeout });
+                }
+                Ok(None) => std::thread::sleep(POLL_INTERVAL),
+                Err(e) => {
+                    procgroup::kill_and_reap(&mut child);
+                    return Err(AuthError::Io(format!("Failed to wait for {program}: {e}")));
+                }
+            }
+        };
+        run.stdin.lock().unwrap().take();
+        // Don't let a background helper holding the pipes open hang us.
+        procgroup::kill_and_reap(&mut child);
+        Ok(Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        })
+    }
+}
+
+/// Reads a child stream to the end on its own thread, passing on each line
+/// as it arrives, and returns everything read. A prompt is written without a
+/// newline and then waits for an answer, so an unfinished line is passed on
+/// as partial once the stream has been quiet for `PARTIAL_AFTER`.
+fn collect(
+    stream: Option<impl Read + Send + 'static>,
+    on_line: impl Fn(&str, bool) + Send + 'static,
+) -> std::thread::JoinHandle<String> {
+    std::thread::spawn(move || {
+        let Some(mut stream) = stream else {
+            return String::new();
+        };
+        let (chunks, received) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 8192];
+            loop {
+                match stream.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if chunks.send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(_) => break,
+                }
+            }
+        });
+        let mut all = Vec::new();
+        let mut lines = LineBuffer::default();
+        loop {
+            match received.recv_timeout(PARTIAL_AFTER) {
+                Ok(chunk) => {
+                    all.extend_from_slice(&chunk);
+                    for line in lines.push(&chunk) {
+                        on_line(&line, false);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Some(partial) = lines.take_partial() {
+                        on_line(&partial, true);
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        if let Some(last) = lines.finish() {
+            on_line(&last, false);
+        }
+        String::from_utf8_lossy(&all).into_owned()
+    })
+}
+
+/// Splits output into lines however it was chunked by the reads.
+#[derive(Default)]
+struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Adds `bytes` and returns the lines they complete.
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(bytes);
+        let mut lines = Vec::new();
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            lines.push(String::from_utf8_lossy(&line[..end]).trim_end_matches('\r').to_string());
+        }
+        lines
+    }
+
+    /// Takes the unfinished line, short of a UTF-8 sequence still missing
+    /// its last bytes, which stays behind for the next read.
+    fn take_partial(&mut self) -> Option<String> {
+        let complete = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        if complete == 0 {
+            return None;
+        }
+        let partial: Vec<u8> = self.pending.drain(..complete).collect();
+        Some(String::from_utf8_lossy(&partial).into_owned())
+    }
+
+    /// The last line at the end of the stream, if it had no newline.
+    fn finish(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let rest = std::mem::take(&mut self.pending);
+        Some(String::from_utf8_lossy(&rest).trim_end_matches('\r').to_string())
+    }
+}
+
+fn detect_url(line: &str) -> Option<String> {
+    let url = URL.find(line)?.as_str();
+    Some(url.trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']']).to_string())
+}
+
+/// A device code on a line of its own, or on a line that says it's a code.
+fn detect_code(line: &str) -> Option<String> {
+    let found = CODE.find(line)?;
+    let alone = found.as_str() == line.trim();
+    (alone || line.to_lowercase().contains("code")).then(|| found.as_str().to_string())
+}
+
+/// Sends each line to the UI along with any URL or code in it, opening the
+/// first URL of the run in the browser if the provider asks for that. Only
+/// whole lines are searched: a partial one may stop halfway through a URL.
+fn output_sink(app: AppHandle, provider: String, open_browser: bool) -> LineSink {
+    let opened_one = AtomicBool::new(false);
+    Arc::new(move |stream, line, partial| {
+        let _ = app.emit(
+            OUTPUT_EVENT,
+            OutputLine {
+                provider: provider.clone(),
+                stream,
+                line: line.to_string(),
+                partial,
+            },
+        );
+        if partial {
+            return;
+        }
+        let url = detect_url(line);
+        let code = detect_code(line);
+        if url.is_none() && code.is_none() {
+            return;
+        }
+        let mut opened = false;
+        if let Some(url) = url.as_deref().filter(|_| open_browser) {
+            if !opened_one.swap(true, Ordering::SeqCst) {
+                match app.opener().open_url(url, None::<&str>) {
+                    Ok(()) => opened = true,
+                    Err(e) => eprintln!("Failed to open {url}: {e}"),
+                }
+            }
+        }
+        let _ = app.emit(
+            PROMPT_EVENT,
+            Prompt {
+                provider: provider.clone(),
+                url,
+                code,
+                opened,
+            },
+        );
+    })
+}
+
+/// Resolves `program` the way a shell would, but against the login `PATH`
+/// rather than the app's.
+fn find_program(program: &str, path: Option<&str>) -> Option<PathBuf> {
+    let candidate = PathBuf::from(program);
+    if candidate.components().count() > 1 {
+        return candidate.is_file().then_some(candidate);
+    }
+    let path = path.map(Into::into).or_else(|| std::env::var_os("PATH"))?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|p| is_executable(p))
+}
+
+fn last_lines(output: &str) -> String {
+    let lines: Vec<&str> = output.trim_end().lines().collect();
+    lines[lines.len().saturating_sub(ERROR_LINES)..].join("\n")
+}
+
+/// Runs the command of auth provider `provider`, with `pin` on its stdin if
+/// it takes one, and returns its stdout.
+#[tauri::command]
+pub async fn authenticate_external(
+    app: AppHandle,
+    provider: String,
+    pin: Option<String>,
+    runs: State<'_, Arc<AuthRuns>>,
+    env_resolver: State<'_, Arc<ShellEnvResolver>>,
+) -> Result<String, AuthError> {
+    let runs = runs.inner().clone();
+    let env_resolver = env_resolver.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let data_dir = crate::data_dir();
+        let settings = DesktopSettings::load(&data_dir).auth;
+        let config = providers(&settings)
+            .into_iter()
+            .find(|p| p.id == provider)
+            .ok_or_else(|| AuthError::UnknownProvider { id: provider.clone() })?;
+        if config.pin_stdin && pin.is_none() {
+            return Err(AuthError::PinRequired);
+        }
+        let runner = Runner::from_provider(&config)?;
+        let run = runs.begin(&provider)?;
+        let env = env_resolver.resolve().vars;
+        let sink = output_sink(app, provider.clone(), config.open_browser);
+        let result = runner.run(pin.as_deref(), &env, &run.run, sink);
+        match &result {
+            Ok(_) => runs.record_success(&data_dir, &provider),
+            Err(e) => eprintln!("External auth with {provider} failed: {e}"),
+        }
+        result
+    })
+    .await
+    .map_err(|e| AuthError::Io(e.to_string()))?
+}
+
+/// The configured auth providers with when each last succeeded.
+#[tauri::command]
+pub fn list_auth_providers(runs: State<'_, Arc<AuthRuns>>) -> Vec<ProviderInfo> {
+    let data_dir = crate::data_dir();
+    let mut history = load_history(&data_dir);
+    providers(&DesktopSettings::load(&data_dir).auth)
+        .into_iter()
+        .map(|p| ProviderInfo {
+            running: runs.is_running(&p.id),
+            last_success: history.remove(&p.id).and_then(|h| h.last_success),
+            name: p.name.unwrap_or_else(|| p.id.clone()),
+            program: p.command.first().cloned(),
+            needs_pin: p.pin_stdin,
+            id: p.id,
+        })
+        .collect()
+}
+
+/// Stops the authentication in progress for `provider`. Returns whether
+/// there was one.
+#[tauri::command]
+pub fn cancel_authentication(provider: String, runs: State<'_, Arc<AuthRuns>>) -> bool {
+    runs.cancel(&provider)
+}
+
+/// Writes `input` and a newline to the stdin of an interactive provider's
+/// command, to answer a prompt it printed.
+#[tauri::command]
+pub fn send_auth_input(
+    provider: String,
+    input: String,
+    runs: State<'_, Arc<AuthRuns>>,
+) -> Result<(), AuthError> {
+    let run = runs.get(&provider).ok_or_else(|| AuthError::NotRunning { id: provider.clone() })?;
+    let mut stdin = run.stdin.lock().unwrap();
+    let Some(stdin) = stdin.as_mut() else {
+        return Err(AuthError::InvalidConfig(format!(
+            "{provider} doesn't take input; set interactive on it"
+        )));
+    };
+    stdin
+        .write_all(format!("{input}\n").as_bytes())
+        .and_then(|()| stdin.flush())
+        .map_err(|e| AuthError::Io(format!("Failed to send input to {provider}: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Yields each chunk after its delay, like a command writing in bursts.
+    struct Bursts(VecDeque<(u64, &'static [u8])>);
+
+    impl Read for Bursts {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let Some((delay_ms, chunk)) = self.0.pop_front() else {
+                return Ok(0);
+            };
+            std::thread::sleep(Duration::from_millis(delay_ms));
+            buf[..chunk.len()].copy_from_slice(chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    #[test]
+    fn detects_a_url_split_across_reads() {
+        let mut lines = LineBuffer::default();
+        assert!(lines.push(b"Open https://device.sso.example.com/st").is_empty());
+        let done = lines.push(b"art?user_code=WXYZ-1234 to continue.\n");
+        assert_eq!(done, ["Open https://device.sso.example.com/start?user_code=WXYZ-1234 to continue."]);
+        assert_eq!(
+            detect_url(&done[0]).as_deref(),
+            Some("https://device.sso.example.com/start?user_code=WXYZ-1234")
+        );
+    }
+
+    #[test]
+    fn detects_a_code_split_across_reads() {
+        let mut lines = LineBuffer::default();
+        assert!(lines.push(b"Your code is ABCD-").is_empty());
+        let done = lines.push(b"EFGH\r\nWaiting");
+        assert_eq!(done, ["Your code is ABCD-EFGH"]);
+        assert_eq!(detect_code(&done[0]).as_deref(), Some("ABCD-EFGH"));
+        assert_eq!(detect_code("ABCD-EFGH").as_deref(), Some("ABCD-EFGH"));
+        assert_eq!(detect_code("build 2024-0101-ABCD done"), None);
+        assert_eq!(lines.finish().as_deref(), Some("Waiting"));
+        assert_eq!(lines.finish(), None);
+    }
+
+    #[test]
+    fn partial_stops_before_a_split_character() {
+        let mut lines = LineBuffer::default();
+        assert!(lines.push(b"Enter the PIN for caf\xc3").is_empty());
+        assert_eq!(lines.take_partial().as_deref(), Some("Enter the PIN for caf"));
+        assert_eq!(lines.take_partial(), None);
+        assert_eq!(lines.push(b"\xa9: \n"), ["é: "]);
+    }
+
+    #[test]
+    fn flushes_a_prompt_once_output_goes_quiet() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let stream = Bursts(VecDeque::from([
+            (0, &b"Open https://example.com/dev"[..]),
+            (20, &b"ice\nPIN: "[..]),
+            (PARTIAL_AFTER.as_millis() as u64 * 3, &b"\nok"[..]),
+        ]));
+        let all = collect(Some(stream), move |line, partial| {
+            sink.lock().unwrap().push((line.to_string(), partial));
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(all, "Open https://example.com/device\nPIN: \nok");
+        let expected = [
+            ("Open https://example.com/device", false),
+            ("PIN: ", true),
+            ("", false),
+            ("ok", false),
+        ]
+        .map(|(line, partial)| (line.to_string(), partial));
+        assert_eq!(*seen.lock().unwrap(), expected);
+    }
+}
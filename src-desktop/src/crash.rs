@@ -0,0 +1,150 @@
+//! Crash reports for a server the supervisor has given up on.
+//!
+//! A report collects the exit history, the last lines of stderr and the
+//! runtime the server ran on. It is saved next to the logs as
+//! `crash-<time>.txt` and shown in a native dialog that offers to restart the
+//! server, open the logs or copy the report. Until the main window is shown
+//! the splash reports failures itself, so no dialog is shown then.
+
+use crate::logs::{self, Stream};
+use crate::startup::Startup;
+use crate::supervisor::{ExitRecord, Supervisor};
+use chrono::{SecondsFormat, Utc};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult};
+
+const RESTART: &str = "Restart Server";
+const OPEN_LOGS: &str = "Open Logs";
+const COPY_REPORT: &str = "Copy Report";
+/// Stderr lines quoted in the dialog itself; the report has them all.
+const DIALOG_LINES: usize = 8;
+
+pub struct CrashReport {
+    created_at: String,
+    reason: String,
+    port: u16,
+    runtime: PathBuf,
+    runtime_version: Option<String>,
+    restarts: u32,
+    exits: Vec<ExitRecord>,
+    stderr: Vec<String>,
+}
+
+impl CrashReport {
+    pub fn new(supervisor: &Supervisor, reason: String) -> Self {
+        let runtime = &supervisor.runtime().candidate;
+        Self {
+            created_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            reason,
+            port: supervisor.port(),
+            runtime: runtime.path.clone(),
+            runtime_version: runtime.version.map(|v| v.to_string()),
+            restarts: supervisor.restarts(),
+            exits: supervisor.exits(),
+            stderr: supervisor.stderr_tail(),
+        }
+    }
+
+    fn summary(&self) -> String {
+        let how = self
+            .exits
+            .last()
+            .map_or_else(|| "stopped".into(), ExitRecord::describe);
+        let mut summary = format!(
+            "The Stallion server {how} and was restarted {} times before giving up ({}).",
+            self.restarts,
+            self.reason.to_lowercase()
+        );
+        let quoted = &self.stderr[self.stderr.len().saturating_sub(DIALOG_LINES)..];
+        if !quoted.is_empty() {
+            summary.push_str("\n\nLast error output:\n");
+            summary.push_str(&quoted.join("\n"));
+        }
+        summary
+    }
+
+    /// The full plain-text report, as saved and copied.
+    pub fn text(&self, app_version: &str) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "Stallion server crash report");
+        let _ = writeln!(text, "Time:     {}", self.created_at);
+        let _ = writeln!(text, "App:      {app_version} ({}/{})", std::env::consts::OS, std::env::consts::ARCH);
+        let _ = writeln!(
+            text,
+            "Node:     {} ({})",
+            self.runtime.display(),
+            self.runtime_version.as_deref().unwrap_or("version unknown")
+        );
+        let _ = writeln!(text, "Port:     {}", self.port);
+        let _ = writeln!(text, "Reason:   {}", self.reason);
+        let _ = writeln!(text, "Restarts: {}", self.restarts);
+        let _ = writeln!(text, "\nExits:");
+        for exit in &self.exits {
+            let _ = writeln!(text, "  {}  {} after {} ms", exit.at, exit.describe(), exit.uptime_ms);
+        }
+        let _ = writeln!(text, "\nLast {} lines of stderr:", self.stderr.len());
+        for line in &self.stderr {
+            let _ = writeln!(text, "  {line}");
+        }
+        text
+    }
+}
+
+/// Saves the report and, once the main window is up, asks the user what to
+/// do about it. Blocks until the dialog is dismissed, so call it off the
+/// main thread.
+pub fn report(app: &AppHandle, report: CrashReport) {
+    let text = report.text(&app.package_info().version.to_string());
+    let data_dir = crate::data_dir();
+    let path = data_dir.join(logs::LOG_DIR).join(format!(
+        "crash-{}.txt",
+        report.created_at.replace(':', "-")
+    ));
+    let note = match std::fs::write(&path, &text) {
+        Ok(()) => format!("Server gave up; crash report saved to {}", path.display()),
+        Err(e) => format!("Server gave up; failed to save crash report: {e}"),
+    };
+    eprintln!("{note}");
+    if let Some(log) = app.try_state::<Arc<logs::ServerLog>>() {
+        log.write_line(Stream::Shell, &note);
+    }
+
+    let Some(startup) = app.try_state::<Arc<Startup>>().map(|s| s.inner().clone()) else {
+        return;
+    };
+    if !startup.handed_off() {
+        return;
+    }
+
+    let choice = app
+        .dialog()
+        .message(report.summary())
+        .title("Stallion server stopped")
+        .kind(MessageDialogKind::Error)
+        .buttons(MessageDialogButtons::YesNoCancelCustom(
+            RESTART.into(),
+            OPEN_LOGS.into(),
+            COPY_REPORT.into(),
+        ))
+        .blocking_show_with_result();
+    // Custom labels come back as-is or as the button they replace; a bare
+    // Cancel is also what dismissing the dialog returns, so it does nothing.
+    let result = match choice {
+        MessageDialogResult::Yes => startup.restart(),
+        MessageDialogResult::No => logs::open_dir(app, &data_dir),
+        MessageDialogResult::Custom(label) if label == RESTART => startup.restart(),
+        MessageDialogResult::Custom(label) if label == OPEN_LOGS => logs::open_dir(app, &data_dir),
+        MessageDialogResult::Custom(label) if label == COPY_REPORT => app
+            .clipboard()
+            .write_text(text)
+            .map_err(|e| format!("Failed to copy crash report: {e}")),
+        _ => Ok(()),
+    };
+    if let Err(e) = result {
+        eprintln!("{e}");
+    }
+}
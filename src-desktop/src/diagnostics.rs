@@ -0,0 +1,312 @@
+//! One-click diagnostics bundle.
+//!
+//! `export_diagnostics` writes a zip for bug reports with everything needed
+//! to answer "why won't it start": the log directory (server output, shell
+//! notes and crash reports), the resolved Node runtime, the login shell
+//! environment, `app.json` and `acp.json`, the installed plugins, the state
+//! of the server's ports and the supervisor's status and exit history.
+//!
+//! Secrets are redacted before anything is written: environment values with
+//! secret-looking names or URL passwords, and everything under
+//! secret-looking keys in the config files.
+
+use crate::logs;
+use crate::node::NodeResolver;
+use crate::port;
+use crate::serverenv;
+use crate::settings::DesktopSettings;
+use crate::shellenv::ShellEnvResolver;
+use crate::startup::Startup;
+use crate::supervisor::ServerSlot;
+use chrono::{Datelike, Local, SecondsFormat, Timelike, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_opener::OpenerExt;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const CONFIG_FILES: &[&str] = &["app.json", "acp.json"];
+const PLUGIN_DIR: &str = "plugins";
+const PLUGIN_MANIFEST: &str = "plugin.json";
+/// Ports after the HTTP port that the server also listens on.
+const EXTRA_PORTS: u16 = 2;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PluginInfo {
+    dir: String,
+    name: Option<String>,
+    version: Option<String>,
+    /// Why the manifest couldn't be read, if it couldn't.
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PortState {
+    port: u16,
+    free: bool,
+    owner: Option<String>,
+}
+
+struct Bundle {
+    zip: ZipWriter<std::fs::File>,
+    options: SimpleFileOptions,
+}
+
+impl Bundle {
+    fn create(path: &Path) -> Result<Self, String> {
+        let file = std::fs::File::create(path)
+            .map_err(|e| format!("Failed to create {}: {e}", path.display()))?;
+        let mut options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        // Entries default to 1980 without a timestamp.
+        let now = Local::now();
+        if let Ok(time) = zip::DateTime::from_date_and_time(
+            now.year().clamp(1980, 2107) as u16,
+            now.month() as u8,
+            now.day() as u8,
+            now.hour() as u8,
+            now.minute() as u8,
+            now.second() as u8,
+        ) {
+            options = options.last_modified_time(time);
+        }
+        Ok(Self {
+            zip: ZipWriter::new(file),
+            options,
+        })
+    }
+
+    fn add(&mut self, name: &str, contents: &[u8]) -> Result<(), String> {
+        self.zip
+            .start_file(name, self.options)
+            .and_then(|()| self.zip.write_all(contents).map_err(Into::into))
+            .map_err(|e| format!("Failed to add {name} to the bundle: {e}"))
+    }
+
+    fn add_json(&mut self, name: &str, value: &impl Serialize) -> Result<(), String> {
+        let json = serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?;
+        self.add(name, &json)
+    }
+
+    fn finish(self) -> Result<(), String> {
+        self.zip
+            .finish()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to finish the bundle: {e}"))
+    }
+}
+
+/// Writes the bundle to `destination`, or to a timestamped file in the
+/// Downloads folder, and returns where it went.
+fn export(
+    app: &AppHandle,
+    startup: &Startup,
+    env_resolver: &ShellEnvResolver,
+    destination: Option<PathBuf>,
+) -> Result<PathBuf, String> {
+    let data_dir = crate::data_dir();
+    let home = std::env::var("HOME").unwrap_or_default();
+    let settings = DesktopSettings::load(&data_dir);
+    let created_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+
+    let path = match destination {
+        Some(path) => path,
+        None => app
+            .path()
+            .download_dir()
+            .or_else(|_| app.path().home_dir())
+            .map_err(|e| e.to_string())?
+            .join(format!("stallion-diagnostics-{}.zip", created_at.replace(':', "-"))),
+    };
+    let mut bundle = Bundle::create(&path)?;
+
+    bundle.add_json(
+        "summary.json",
+        &json!({
+            "createdAt": created_at,
+            "appVersion": app.package_info().version.to_string(),
+            "os": std::env::consts::OS,
+            "arch": std::env::consts::ARCH,
+            "dataDir": data_dir,
+        }),
+    )?;
+
+    for file in log_files(&data_dir.join(logs::LOG_DIR)) {
+        let Some(name) = file.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        match std::fs::read(&file) {
+            Ok(contents) => bundle.add(&format!("logs/{name}"), &contents)?,
+            Err(e) => eprintln!("Skipping {} in diagnostics: {e}", file.display()),
+        }
+    }
+
+    let shell_env = env_resolver.resolve();
+    let vars: serde_json::Map<String, Value> = shell_env
+        .vars
+        .iter()
+        .map(|(name, value)| (name.clone(), Value::String(serverenv::redact(name, value))))
+        .collect();
+    bundle.add_json("shell-env.json", &json!({ "summary": shell_env.summary(), "vars": vars }))?;
+
+    // The running server's runtime if there is one, otherwise what a start
+    // would pick now (or why it can't pick anything).
+    let current = app.state::<ServerSlot>().current();
+    let runtime = match &current {
+        Some(supervisor) => json!({ "inUse": true, "resolution": supervisor.runtime() }),
+        None => match NodeResolver::new(Path::new(&home), &settings.runtime, &shell_env.vars).resolve() {
+            Ok(resolution) => json!({ "inUse": false, "resolution": resolution }),
+            Err(e) => json!({ "inUse": false, "error": e.to_string() }),
+        },
+    };
+    bundle.add_json("node.json", &runtime)?;
+
+    for name in CONFIG_FILES {
+        let path = data_dir.join("config").join(name);
+        let Ok(raw) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        match serde_json::from_str::<Value>(&raw) {
+            Ok(mut value) => {
+                redact_json(&mut value);
+                bundle.add_json(&format!("config/{name}"), &value)?;
+            }
+            Err(e) => {
+                let note = format!("Not valid JSON: {e}\n");
+                bundle.add(&format!("config/{name}.error.txt"), note.as_bytes())?
+            }
+        }
+    }
+
+    bundle.add_json("plugins.json", &plugins(&data_dir.join(PLUGIN_DIR)))?;
+
+    let status = startup.server_status();
+    let ports: Vec<PortState> = status
+        .port
+        .map(|first| (first..=first.saturating_add(EXTRA_PORTS)).map(port_state).collect())
+        .unwrap_or_default();
+    bundle.add_json("ports.json", &ports)?;
+
+    bundle.add_json(
+        "supervisor.json",
+        &json!({
+            "status": status,
+            "exits": current.as_ref().map(|s| s.exits()).unwrap_or_default(),
+            "startup": startup.status(),
+            "settings": settings.server,
+        }),
+    )?;
+
+    bundle.finish()?;
+    Ok(path)
+}
+
+fn log_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| p.is_file()).collect();
+    files.sort();
+    files
+}
+
+fn plugins(dir: &Path) -> Vec<PluginInfo> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut plugins: Vec<PluginInfo> = entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .map(|entry| {
+            let dir = entry.file_name().to_string_lossy().into_owned();
+            let manifest = std::fs::read_to_string(entry.path().join(PLUGIN_MANIFEST))
+                .map_err(|e| e.to_string())
+                .and_then(|raw| serde_json::from_str::<Value>(&raw).map_err(|e| e.to_string()));
+            match manifest {
+                Ok(manifest) => {
+                    let field = |key: &str| manifest.get(key).and_then(Value::as_str).map(String::from);
+                    PluginInfo {
+                        dir,
+                        name: field("name"),
+                        version: field("version"),
+                        error: None,
+                    }
+                }
+                Err(e) => PluginInfo {
+                    dir,
+                    name: None,
+                    version: None,
+                    error: Some(format!("{PLUGIN_MANIFEST}: {e}")),
+                },
+            }
+        })
+        .collect();
+    plugins.sort_by(|a, b| a.dir.cmp(&b.dir));
+    plugins
+}
+
+fn port_state(port: u16) -> PortState {
+    let free = port::is_free(port);
+    PortState {
+        port,
+        free,
+        owner: if free { None } else { port::find_owner(port).map(|o| o.name()) },
+    }
+}
+
+/// Redacts every string under a secret-looking key, and URL passwords in any
+/// other string, throughout a config document.
+fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match value {
+                    Value::String(s) => *s = serverenv::redact(key, s),
+                    Value::Object(_) | Value::Array(_) if serverenv::is_secret_name(key) => {
+                        redact_all(value)
+                    }
+                    other => redact_json(other),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_json),
+        Value::String(s) => *s = serverenv::redact("", s),
+        _ => {}
+    }
+}
+
+fn redact_all(value: &mut Value) {
+    match value {
+        Value::Object(map) => map.values_mut().for_each(redact_all),
+        Value::Array(items) => items.iter_mut().for_each(redact_all),
+        Value::String(s) => *s = serverenv::REDACTED.to_string(),
+        _ => {}
+    }
+}
+
+/// Writes the diagnostics bundle and shows it in the file manager.
+/// `destination` defaults to the Downloads folder.
+#[tauri::command]
+pub async fn export_diagnostics(
+    app: AppHandle,
+    startup: State<'_, Arc<Startup>>,
+    env_resolver: State<'_, Arc<ShellEnvResolver>>,
+    destination: Option<PathBuf>,
+) -> Result<PathBuf, String> {
+    let startup = startup.inner().clone();
+    let env_resolver = env_resolver.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = export(&app, &startup, &env_resolver, destination)?;
+        if let Err(e) = app.opener().reveal_item_in_dir(&path) {
+            eprintln!("Failed to reveal {}: {e}", path.display());
+        }
+        Ok(path)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
@@ -0,0 +1,61 @@
+//! The shape command errors reach the frontend in.
+//!
+//! Commands that fail in more than one way the UI cares about return an
+//! error serialized as `{ kind, message }`: `kind` is a stable camelCase tag
+//! to branch on, `message` the `Display` text to show.
+
+use serde::ser::{SerializeStruct, Serializer};
+use std::fmt;
+
+pub trait CommandError: fmt::Display {
+    fn kind(&self) -> &'static str;
+}
+
+/// Serializes `error` as `{ kind, message }`.
+pub fn serialize<E: CommandError, S: Serializer>(error: &E, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("CommandError", 2)?;
+    state.serialize_field("kind", error.kind())?;
+    state.serialize_field("message", &error.to_string())?;
+    state.end()
+}
+
+/// Implements `Serialize` through [`serialize`] for a [`CommandError`].
+macro_rules! serialize_command_error {
+    ($error:ty) => {
+        impl serde::Serialize for $error {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                $crate::error::serialize(self, serializer)
+            }
+        }
+    };
+}
+pub(crate) use serialize_command_error;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Example;
+
+    impl fmt::Display for Example {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("Something went wrong")
+        }
+    }
+
+    impl CommandError for Example {
+        fn kind(&self) -> &'static str {
+            "example"
+        }
+    }
+
+    serialize_command_error!(Example);
+
+    #[test]
+    fn serializes_kind_and_message() {
+        assert_eq!(
+            serde_json::to_value(Example).unwrap(),
+            serde_json::json!({ "kind": "example", "message": "Something went wrong" })
+        );
+    }
+}
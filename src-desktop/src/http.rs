@@ -0,0 +1,139 @@
+//! HTTP clients.
+//!
+//! All of the shell's HTTP goes through reqwest: small synchronous requests
+//! to its own server on 127.0.0.1, and async ones to OAuth endpoints.
+
+use reqwest::blocking;
+use reqwest::header::CONTENT_TYPE;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub enum Response {
+    /// Nothing is listening on the port.
+    Refused,
+    /// Connected, but the request failed or timed out.
+    Failed,
+    Ok { status: u16, body: Vec<u8> },
+}
+
+/// Talks to the local server directly, whatever proxy the login shell sets.
+static LOCAL: LazyLock<Result<blocking::Client, String>> = LazyLock::new(|| {
+    install_crypto();
+    blocking::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .no_proxy()
+        .build()
+        .map_err(|e| e.to_string())
+});
+
+/// `GET`s `path` from the server on `port`. Like [`post_json`], this must not
+/// be called from async code: the blocking client runs a runtime of its own.
+pub fn get(port: u16, path: &str, timeout: Duration) -> Response {
+    send(|client| client.get(local_url(port, path)).timeout(timeout))
+}
+
+/// `POST`s a JSON `body` to `path` on the server on `port`.
+pub fn post_json(port: u16, path: &str, body: String, timeout: Duration) -> Response {
+    send(|client| {
+        client
+            .post(local_url(port, path))
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .timeout(timeout)
+    })
+}
+
+fn local_url(port: u16, path: &str) -> String {
+    format!("http://127.0.0.1:{port}{path}")
+}
+
+fn send(request: impl FnOnce(&blocking::Client) -> blocking::RequestBuilder) -> Response {
+    let client = match LOCAL.as_ref() {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Failed to create the HTTP client: {e}");
+            return Response::Failed;
+        }
+    };
+    let response = match request(client).send() {
+        Ok(response) => response,
+        Err(e) if e.is_connect() => return Response::Refused,
+        Err(_) => return Response::Failed,
+    };
+    let status = response.status().as_u16();
+    match response.bytes() {
+        Ok(body) => Response::Ok { status, body: body.to_vec() },
+        Err(_) => Response::Failed,
+    }
+}
+
+/// A client for requests that leave the machine, honouring proxies.
+pub fn client(timeout: Duration) -> Result<reqwest::Client, String> {
+    install_crypto();
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// reqwest is built without a TLS crypto provider of its own.
+fn install_crypto() {
+    if rustls::crypto::CryptoProvider::get_default().is_none() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{Ipv4Addr, TcpListener};
+
+    /// Answers one request with `response` and returns the request head.
+    fn serve_once(response: &'static str) -> (u16, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut head = Vec::new();
+            let mut byte = [0u8; 1];
+            while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+                head.push(byte[0]);
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+            String::from_utf8(head).unwrap()
+        });
+        (port, server)
+    }
+
+    #[test]
+    fn reads_status_and_chunked_body() {
+        let (port, server) = serve_once(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+             5\r\n{\"ok\"\r\n6\r\n:true}\r\n0\r\n\r\n",
+        );
+        let Response::Ok { status, body } = get(port, "/api/system/status", Duration::from_secs(5)) else {
+            panic!("expected a response");
+        };
+        assert_eq!((status, body.as_slice()), (200, &b"{\"ok\":true}"[..]));
+        assert!(server.join().unwrap().starts_with("GET /api/system/status HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn posts_json() {
+        let (port, server) = serve_once("HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n");
+        let response = post_json(port, "/api/tokens", "{}".into(), Duration::from_secs(5));
+        assert!(matches!(response, Response::Ok { status: 204, .. }));
+        let head = server.join().unwrap().to_lowercase();
+        assert!(head.starts_with("post /api/tokens http/1.1\r\n"));
+        assert!(head.contains("content-type: application/json\r\n"));
+    }
+
+    #[test]
+    fn reports_a_closed_port_as_refused() {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port();
+        assert!(matches!(get(port, "/", Duration::from_secs(1)), Response::Refused));
+    }
+}
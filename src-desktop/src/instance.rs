@@ -0,0 +1,123 @@
+//! Single-instance enforcement.
+//!
+//! The first instance listens on a Unix socket in the data dir. A later
+//! launch connects to it, forwards its CLI arguments and working directory,
+//! and exits before starting a competing server. The running instance
+//! focuses its main window (or the splash while starting up) and emits the
+//! arguments on `app://second-instance`.
+//!
+//! A socket file left behind by a crashed or force-quit instance is detected
+//! by the next launch (nothing accepts the connection) and replaced.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const SECOND_INSTANCE_EVENT: &str = "app://second-instance";
+#[cfg(unix)]
+const SOCKET_FILE: &str = "desktop.sock";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LaunchRequest {
+    pub args: Vec<String>,
+    pub cwd: Option<PathBuf>,
+}
+
+impl LaunchRequest {
+    fn current() -> Self {
+        Self {
+            args: std::env::args().skip(1).collect(),
+            cwd: std::env::current_dir().ok(),
+        }
+    }
+}
+
+pub enum Acquired {
+    /// This is the only instance; keep the lock alive for the app's lifetime.
+    Primary(InstanceLock),
+    /// Another instance is running and has received this launch's arguments.
+    Forwarded,
+}
+
+pub struct InstanceLock {
+    #[cfg(unix)]
+    listener: Option<std::os::unix::net::UnixListener>,
+}
+
+#[cfg(unix)]
+pub fn acquire(data_dir: &Path) -> Acquired {
+    use std::io::Write;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let path = data_dir.join(SOCKET_FILE);
+    let _ = std::fs::create_dir_all(data_dir);
+
+    // Two attempts: a launch racing us may bind between our stale-socket
+    // cleanup and our own bind.
+    for _ in 0..2 {
+        if let Ok(mut stream) = UnixStream::connect(&path) {
+            let mut payload = serde_json::to_vec(&LaunchRequest::current()).unwrap_or_default();
+            payload.push(b'\n');
+            if stream.write_all(&payload).is_ok() {
+                return Acquired::Forwarded;
+            }
+        }
+        // Nobody answered, so any socket file left behind is stale.
+        let _ = std::fs::remove_file(&path);
+        match UnixListener::bind(&path) {
+            Ok(listener) => {
+                return Acquired::Primary(InstanceLock {
+                    listener: Some(listener),
+                })
+            }
+            Err(e) => eprintln!("Failed to bind {}: {e}", path.display()),
+        }
+    }
+    // Couldn't bind or forward; run anyway rather than refusing to start.
+    Acquired::Primary(InstanceLock { listener: None })
+}
+
+#[cfg(not(unix))]
+pub fn acquire(_data_dir: &Path) -> Acquired {
+    Acquired::Primary(InstanceLock {})
+}
+
+impl InstanceLock {
+    /// Serves launch requests from later instances on a background thread.
+    #[cfg(unix)]
+    pub fn listen(&mut self, app: AppHandle) {
+        use std::io::{BufRead, BufReader};
+
+        let Some(listener) = self.listener.take() else {
+            return;
+        };
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut line = String::new();
+                if BufReader::new(stream).read_line(&mut line).is_err() {
+                    continue;
+                }
+                match serde_json::from_str::<LaunchRequest>(&line) {
+                    Ok(request) => on_second_instance(&app, request),
+                    Err(e) => eprintln!("Ignoring malformed launch request: {e}"),
+                }
+            }
+        });
+    }
+
+    #[cfg(not(unix))]
+    pub fn listen(&mut self, _app: AppHandle) {}
+}
+
+fn on_second_instance(app: &AppHandle, request: LaunchRequest) {
+    // The splash only exists until the main window is shown.
+    let window = app
+        .get_webview_window(crate::startup::SPLASH_WINDOW)
+        .or_else(|| app.get_webview_window(crate::startup::MAIN_WINDOW));
+    if let Some(window) = window {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+    let _ = app.emit(SECOND_INSTANCE_EVENT, request);
+}
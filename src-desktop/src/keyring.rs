@@ -0,0 +1,169 @@
+//! Minimal client for the freedesktop Secret Service, which GNOME Keyring,
+//! KWallet and KeePassXC all provide on the session D-Bus.
+//!
+//! Items go into the default collection and are found again by their
+//! attributes. Secrets cross the bus in a "plain" session, which only the
+//! user's own processes can reach. When a collection or item is locked, the
+//! daemon shows its own unlock prompt and the call waits for it.
+
+use std::collections::HashMap;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+const SERVICE: &str = "org.freedesktop.secrets";
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const SERVICE_IFACE: &str = "org.freedesktop.Secret.Service";
+const COLLECTION_IFACE: &str = "org.freedesktop.Secret.Collection";
+const ITEM_IFACE: &str = "org.freedesktop.Secret.Item";
+const PROMPT_IFACE: &str = "org.freedesktop.Secret.Prompt";
+/// The path the service returns when no prompt is needed.
+const NO_PROMPT: &str = "/";
+const CONTENT_TYPE: &str = "text/plain; charset=utf8";
+
+/// Session, parameters, value and content type, as the service sends them.
+type Secret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+pub struct Keyring {
+    conn: Connection,
+    session: OwnedObjectPath,
+}
+
+impl Keyring {
+    /// Opens a session with the Secret Service; fails when there is no
+    /// session bus or no keyring daemon on it.
+    pub fn connect() -> Result<Self, String> {
+        let conn = Connection::session().map_err(|e| format!("No D-Bus session bus: {e}"))?;
+        let (_, session): (OwnedValue, OwnedObjectPath) = Proxy::new(&conn, SERVICE, SERVICE_PATH, SERVICE_IFACE)
+            .and_then(|service| service.call("OpenSession", &("plain", Value::from(""))))
+            .map_err(|e| format!("No Secret Service: {e}"))?;
+        Ok(Self { conn, session })
+    }
+
+    fn proxy(&self, path: &OwnedObjectPath, interface: &'static str) -> Result<Proxy<'_>, String> {
+        Proxy::new(&self.conn, SERVICE, path.clone().into_inner(), interface).map_err(|e| e.to_string())
+    }
+
+    fn service(&self) -> Result<Proxy<'_>, String> {
+        Proxy::new(&self.conn, SERVICE, SERVICE_PATH, SERVICE_IFACE).map_err(|e| e.to_string())
+    }
+
+    /// Unlocked and locked items with all of `attributes`.
+    fn search(&self, attributes: &HashMap<&str, &str>) -> Result<Vec<(OwnedObjectPath, bool)>, String> {
+        let (unlocked, locked): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) = self
+            .service()?
+            .call("SearchItems", &(attributes,))
+            .map_err(|e| format!("Keyring search failed: {e}"))?;
+        Ok(unlocked
+            .into_iter()
+            .map(|item| (item, false))
+            .chain(locked.into_iter().map(|item| (item, true)))
+            .collect())
+    }
+
+    fn unlock(&self, objects: Vec<OwnedObjectPath>) -> Result<(), String> {
+        let (_, prompt): (Vec<OwnedObjectPath>, OwnedObjectPath) = self
+            .service()?
+            .call("Unlock", &(objects,))
+            .map_err(|e| format!("Failed to unlock the keyring: {e}"))?;
+        self.prompt(&prompt)
+    }
+
+    /// Waits for the user to answer a prompt the service asked for.
+    fn prompt(&self, path: &OwnedObjectPath) -> Result<(), String> {
+        if path.as_str() == NO_PROMPT {
+            return Ok(());
+        }
+        let prompt = self.proxy(path, PROMPT_IFACE)?;
+        let mut completed = prompt.receive_signal("Completed").map_err(|e| e.to_string())?;
+        prompt
+            .call_method("Prompt", &("",))
+            .map_err(|e| format!("Failed to show the keyring prompt: {e}"))?;
+        let message = completed
+            .next()
+            .ok_or_else(|| "The keyring prompt went away".to_string())?;
+        let (dismissed, _): (bool, OwnedValue) = message.body().deserialize().map_err(|e| e.to_string())?;
+        if dismissed {
+            Err("The keyring prompt was dismissed".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The secret of the first item with all of `attributes`.
+    pub fn get(&self, attributes: &HashMap<&str, &str>) -> Result<Option<String>, String> {
+        let Some((item, locked)) = self.search(attributes)?.into_iter().next() else {
+            return Ok(None);
+        };
+        if locked {
+            self.unlock(vec![item.clone()])?;
+        }
+        let (_, _, value, _): Secret = self
+            .proxy(&item, ITEM_IFACE)?
+            .call("GetSecret", &(&self.session,))
+            .map_err(|e| format!("Failed to read from the keyring: {e}"))?;
+        String::from_utf8(value)
+            .map(Some)
+            .map_err(|_| "The keyring item is not text".into())
+    }
+
+    /// Stores `value` in the default collection, replacing any item with the
+    /// same attributes.
+    pub fn set(&self, label: &str, attributes: &HashMap<&str, &str>, value: &str) -> Result<(), String> {
+        let collection: OwnedObjectPath = self
+            .service()?
+            .call("ReadAlias", &("default",))
+            .map_err(|e| format!("Failed to find the default keyring: {e}"))?;
+        if collection.as_str() == NO_PROMPT {
+            return Err("The keyring has no default collection".into());
+        }
+        let proxy = self.proxy(&collection, COLLECTION_IFACE)?;
+        if proxy.get_property::<bool>("Locked").map_err(|e| e.to_string())? {
+            self.unlock(vec![collection.clone()])?;
+        }
+
+        let attributes: HashMap<String, String> = attributes
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let properties = HashMap::from([
+            ("org.freedesktop.Secret.Item.Label", Value::from(label)),
+            ("org.freedesktop.Secret.Item.Attributes", Value::from(attributes)),
+        ]);
+        let secret = (&self.session, Vec::<u8>::new(), value.as_bytes(), CONTENT_TYPE);
+        let (_, prompt): (OwnedObjectPath, OwnedObjectPath) = proxy
+            .call("CreateItem", &(properties, secret, true))
+            .map_err(|e| format!("Failed to write to the keyring: {e}"))?;
+        self.prompt(&prompt)
+    }
+
+    /// Deletes every item with all of `attributes`, returning how many there
+    /// were.
+    pub fn delete(&self, attributes: &HashMap<&str, &str>) -> Result<usize, String> {
+        let items = self.search(attributes)?;
+        let locked: Vec<OwnedObjectPath> = items.iter().filter(|(_, l)| *l).map(|(i, _)| i.clone()).collect();
+        if !locked.is_empty() {
+            self.unlock(locked)?;
+        }
+        for (item, _) in &items {
+            let prompt: OwnedObjectPath = self
+                .proxy(item, ITEM_IFACE)?
+                .call("Delete", &())
+                .map_err(|e| format!("Failed to delete from the keyring: {e}"))?;
+            self.prompt(&prompt)?;
+        }
+        Ok(items.len())
+    }
+
+    /// The attributes of every item with all of `attributes`. Attributes are
+    /// readable without unlocking.
+    pub fn list(&self, attributes: &HashMap<&str, &str>) -> Result<Vec<HashMap<String, String>>, String> {
+        self.search(attributes)?
+            .iter()
+            .map(|(item, _)| {
+                self.proxy(item, ITEM_IFACE)?
+                    .get_property::<HashMap<String, String>>("Attributes")
+                    .map_err(|e| e.to_string())
+            })
+            .collect()
+    }
+}
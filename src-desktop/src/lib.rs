@@ -1,25 +1,71 @@
-use std::sync::{Arc, Mutex};
 use tauri::Manager;
 
 #[cfg(not(mobile))]
-use std::process::{Child, Command, Stdio};
+mod auth;
 #[cfg(not(mobile))]
-use std::sync::atomic::Ordering;
+mod crash;
+#[cfg(not(mobile))]
+mod diagnostics;
+#[cfg(not(mobile))]
+mod error;
+#[cfg(not(mobile))]
+mod http;
+#[cfg(not(mobile))]
+mod instance;
+#[cfg(target_os = "linux")]
+mod keyring;
+#[cfg(not(mobile))]
+mod lifecycle;
+#[cfg(not(mobile))]
+mod logs;
+#[cfg(not(mobile))]
+mod node;
+#[cfg(not(mobile))]
+mod oauth;
+#[cfg(not(mobile))]
+mod port;
+#[cfg(not(mobile))]
+mod procgroup;
 #[cfg(not(mobile))]
-use std::time::{Duration, Instant};
+mod readiness;
 #[cfg(not(mobile))]
-use std::net::TcpStream;
+mod runtime;
+#[cfg(not(mobile))]
+mod secrets;
+#[cfg(not(mobile))]
+mod seed;
+#[cfg(not(mobile))]
+mod serverenv;
+#[cfg(not(mobile))]
+mod settings;
+#[cfg(not(mobile))]
+mod shellenv;
+#[cfg(not(mobile))]
+mod startup;
+#[cfg(not(mobile))]
+mod supervisor;
+
+#[cfg(not(mobile))]
+use std::sync::Arc;
+#[cfg(not(mobile))]
+use std::sync::atomic::Ordering;
 #[cfg(not(mobile))]
 use tauri::WebviewUrl;
 #[cfg(not(mobile))]
 use tauri::WebviewWindowBuilder;
 #[cfg(not(mobile))]
-use tauri_plugin_shell::ShellExt;
+use logs::ServerLog;
+#[cfg(not(mobile))]
+use settings::DesktopSettings;
+#[cfg(not(mobile))]
+use shellenv::ShellEnvResolver;
+#[cfg(not(mobile))]
+use startup::Startup;
+#[cfg(not(mobile))]
+use supervisor::ServerSlot;
 
 #[cfg(not(mobile))]
 static WINDOW_COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
-#[cfg(not(mobile))]
-const PORT: u16 = 3142;
 
 #[cfg(not(mobile))]
 #[tauri::command]
@@ -38,113 +84,30 @@ fn open_research_url(app: tauri::AppHandle, url: String, title: String) -> Resul
 }
 
 #[cfg(not(mobile))]
-#[tauri::command]
-async fn authenticate_external(app: tauri::AppHandle, pin: String) -> Result<String, String> {
-    let auth_cmd = std::env::var("AUTH_COMMAND").map_err(|_|
-        "No AUTH_COMMAND environment variable configured.".to_string()
-    )?;
-
-    let shell = app.shell();
-    let output = shell
-        .command("sh")
-        .args(["-c", &format!("echo '{}' | {} 2>&1", pin, auth_cmd)])
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute auth command: {}", e))?;
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
-
-    if output.status.success() || stdout.contains("successfully") || stdout.contains("authenticated") {
-        Ok(stdout.to_string())
-    } else {
-        Err(format!("{}\n{}", stdout, stderr))
-    }
-}
-
-#[cfg(not(mobile))]
-fn resolve_shell_path() -> String {
-    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/zsh".into());
-    if let Ok(output) = Command::new(&shell)
-        .args(["-ilc", "echo $PATH"])
-        .stderr(Stdio::null())
-        .output()
-    {
-        let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if !path.is_empty() { return path; }
-    }
-    std::env::var("PATH").unwrap_or_default()
-}
-
-#[cfg(not(mobile))]
-fn find_node() -> String {
+fn data_dir() -> std::path::PathBuf {
     let home = std::env::var("HOME").unwrap_or_default();
-
-    let mise_dir = format!("{home}/.local/share/mise/installs/node");
-    if let Ok(entries) = std::fs::read_dir(&mise_dir) {
-        let mut versions: Vec<_> = entries.filter_map(|e| e.ok()).collect();
-        versions.sort_by(|a, b| b.file_name().cmp(&a.file_name()));
-        if let Some(entry) = versions.first() {
-            let bin = entry.path().join("bin/node");
-            if bin.exists() { return bin.to_string_lossy().into(); }
-        }
-    }
-
-    for c in [
-        format!("{home}/.nvm/current/bin/node"),
-        format!("{home}/.volta/bin/node"),
-        "/opt/homebrew/bin/node".into(),
-        "/usr/local/bin/node".into(),
-    ] {
-        if std::path::Path::new(&c).exists() { return c; }
-    }
-    "node".into()
-}
-
-#[cfg(not(mobile))]
-fn kill_port(port: u16) {
-    if let Ok(output) = Command::new("lsof")
-        .args(["-ti", &format!(":{port}")])
-        .output()
-    {
-        let pids = String::from_utf8_lossy(&output.stdout);
-        for pid in pids.split_whitespace() {
-            let _ = Command::new("kill").arg(pid.trim()).output();
-        }
-        if !pids.trim().is_empty() {
-            std::thread::sleep(Duration::from_millis(500));
-        }
-    }
-}
-
-#[cfg(not(mobile))]
-fn wait_for_port(port: u16, timeout: Duration) -> bool {
-    let start = Instant::now();
-    while start.elapsed() < timeout {
-        if TcpStream::connect(("127.0.0.1", port)).is_ok() {
-            return true;
-        }
-        std::thread::sleep(Duration::from_millis(200));
-    }
-    false
+    std::path::PathBuf::from(home).join(".stallion-ai")
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // A second launch hands its arguments to the running app and exits
+    // before it can start a competing server.
     #[cfg(not(mobile))]
-    let server_child: Arc<Mutex<Option<Child>>> = Arc::new(Mutex::new(None));
-    #[cfg(not(mobile))]
-    let child_for_setup = server_child.clone();
-    #[cfg(not(mobile))]
-    let child_for_exit = server_child.clone();
-    #[cfg(not(mobile))]
-    let node = find_node();
+    let mut instance = match instance::acquire(&data_dir()) {
+        instance::Acquired::Primary(lock) => lock,
+        instance::Acquired::Forwarded => return,
+    };
 
     let mut builder = tauri::Builder::default();
 
     #[cfg(not(mobile))]
     {
-        builder = builder.plugin(tauri_plugin_shell::init());
+        builder = builder
+            .plugin(tauri_plugin_shell::init())
+            .plugin(tauri_plugin_dialog::init())
+            .plugin(tauri_plugin_opener::init())
+            .plugin(tauri_plugin_clipboard_manager::init());
     }
 
     builder
@@ -152,75 +115,145 @@ pub fn run() {
             #[cfg(not(mobile))]
             open_research_url,
             #[cfg(not(mobile))]
-            authenticate_external,
+            auth::authenticate_external,
+            #[cfg(not(mobile))]
+            auth::cancel_authentication,
+            #[cfg(not(mobile))]
+            auth::list_auth_providers,
+            #[cfg(not(mobile))]
+            auth::send_auth_input,
+            #[cfg(not(mobile))]
+            oauth::start_oauth_flow,
+            #[cfg(not(mobile))]
+            oauth::cancel_oauth_flow,
+            #[cfg(not(mobile))]
+            secrets::secret_set,
+            #[cfg(not(mobile))]
+            secrets::secret_get,
+            #[cfg(not(mobile))]
+            secrets::secret_delete,
+            #[cfg(not(mobile))]
+            secrets::secret_list,
+            #[cfg(not(mobile))]
+            logs::tail_server_logs,
+            #[cfg(not(mobile))]
+            logs::search_server_logs,
+            #[cfg(not(mobile))]
+            shellenv::refresh_shell_env,
+            #[cfg(not(mobile))]
+            serverenv::get_env_profile,
+            #[cfg(not(mobile))]
+            serverenv::save_env_profile,
+            #[cfg(not(mobile))]
+            serverenv::get_server_env,
+            #[cfg(not(mobile))]
+            startup::startup_status,
+            #[cfg(not(mobile))]
+            startup::retry_startup,
+            #[cfg(not(mobile))]
+            startup::open_logs,
+            #[cfg(not(mobile))]
+            startup::server_status,
+            #[cfg(not(mobile))]
+            startup::start_server,
+            #[cfg(not(mobile))]
+            startup::stop_server,
+            #[cfg(not(mobile))]
+            startup::restart_server,
+            #[cfg(not(mobile))]
+            diagnostics::export_diagnostics,
         ])
         .setup(move |app| {
             #[cfg(not(mobile))]
             {
                 let resource_path = app.path().resource_dir().expect("failed to get resource dir");
-                let server_path = resource_path.join("dist-server").join("index.js");
-                let log_dir = std::env::temp_dir();
+                instance.listen(app.handle().clone());
+
                 let home = std::env::var("HOME").unwrap_or_default();
-                let data_dir = std::path::PathBuf::from(&home).join(".stallion-ai");
+                let data_dir = data_dir();
 
-                let bundled_seed = resource_path.join("seed");
-                if bundled_seed.exists() && !data_dir.exists() {
-                    let _ = std::fs::create_dir_all(&data_dir);
-                    let _ = Command::new("cp").args(["-R",
-                        bundled_seed.join("config").to_str().unwrap_or(""),
-                        data_dir.join("config").to_str().unwrap_or("")
-                    ]).output();
-                }
+                let settings = DesktopSettings::load(&data_dir);
+                let log = Arc::new(ServerLog::open(app.handle().clone(), &data_dir, settings.logs.clone()));
+                app.manage(log.clone());
 
-                kill_port(PORT);
-                let shell_path = resolve_shell_path();
-
-                let child = Command::new(&node)
-                    .arg(&server_path)
-                    .current_dir(&resource_path)
-                    .env("STALLION_AI_DIR", &data_dir)
-                    .env("PORT", PORT.to_string())
-                    .env("PATH", &shell_path)
-                    .env("HOME", &home)
-                    .stdout(std::fs::File::create(log_dir.join("stallion-server.log")).map_or(
-                        Stdio::null(), Stdio::from))
-                    .stderr(std::fs::File::create(log_dir.join("stallion-server-err.log")).map_or(
-                        Stdio::null(), Stdio::from))
-                    .spawn();
-
-                match child {
-                    Ok(c) => {
-                        *child_for_setup.lock().unwrap() = Some(c);
-                        if !wait_for_port(PORT, Duration::from_secs(10)) {
-                            eprintln!("Server did not become ready within 10s");
+                let bundled_seed = resource_path.join("seed");
+                if bundled_seed.exists() {
+                    match seed::apply(&bundled_seed, &data_dir) {
+                        Ok(changes) => {
+                            for change in changes {
+                                let note = format!("Seed: {change}");
+                                eprintln!("{note}");
+                                log.write_line(logs::Stream::Shell, &note);
+                            }
                         }
+                        Err(e) => eprintln!("Failed to apply seed configs: {e}"),
                     }
-                    Err(e) => eprintln!("Failed to spawn server: {e}"),
                 }
+                let env_resolver = Arc::new(ShellEnvResolver::new(
+                    std::path::Path::new(&home),
+                    &data_dir,
+                    settings.shell_env.clone(),
+                ));
+                app.manage(env_resolver.clone());
+                app.manage(ServerSlot::default());
+                app.manage(Arc::new(auth::AuthRuns::default()));
+                app.manage(Arc::new(oauth::OAuthFlows::default()));
+                app.manage(Arc::new(secrets::Secrets::new(data_dir.clone())));
 
-                // Inject API base so the frontend connects to the desktop port
-                if let Some(window) = app.get_webview_window("main") {
-                    let _ = window.eval(&format!(
-                        "window.__API_BASE__ = 'http://localhost:{PORT}';"
-                    ));
-                }
+                let startup = Startup::new(
+                    app.handle().clone(),
+                    resource_path,
+                    data_dir,
+                    home,
+                    log,
+                    env_resolver,
+                );
+                app.manage(startup.clone());
+                startup.run();
             }
 
             let _ = app;
             Ok(())
         })
-        .on_window_event(move |_window, event| {
+        .on_window_event(move |window, event| {
+            // Closing the last window quits the app, which may need confirming;
+            // closing any other window (e.g. research-N) leaves the server alone.
             #[cfg(not(mobile))]
-            if let tauri::WindowEvent::Destroyed = event {
-                if let Ok(mut guard) = child_for_exit.lock() {
-                    if let Some(ref mut child) = *guard {
-                        let _ = child.kill();
-                    }
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                let app = window.app_handle();
+                // Before hand-off the main window is hidden, so closing the
+                // splash is closing the app.
+                let last = app.webview_windows().len() <= 1
+                    || (window.label() == startup::SPLASH_WINDOW
+                        && !app.state::<Arc<Startup>>().handed_off());
+                if last && !lifecycle::quit_confirmed() {
+                    api.prevent_close();
+                    lifecycle::request_quit(app);
                 }
             }
             #[cfg(mobile)]
-            let _ = event;
+            let _ = (window, event);
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            #[cfg(not(mobile))]
+            match event {
+                // Quit from the menu or keyboard while windows are still open.
+                tauri::RunEvent::ExitRequested { api, .. }
+                    if !app.webview_windows().is_empty() && !lifecycle::quit_confirmed() =>
+                {
+                    api.prevent_exit();
+                    lifecycle::request_quit(app);
+                }
+                tauri::RunEvent::Exit => {
+                    if let Some(supervisor) = app.try_state::<ServerSlot>().and_then(|s| s.current()) {
+                        supervisor.shutdown();
+                    }
+                }
+                _ => {}
+            }
+            #[cfg(mobile)]
+            let _ = (app, event);
+        });
 }
@@ -0,0 +1,106 @@
+//! App-level quit handling.
+//!
+//! The server lives as long as the app, not as long as any one window: it is
+//! stopped on `RunEvent::Exit` only. Before quitting, the shell asks the
+//! server whether chats are streaming or scheduled jobs are running and, if
+//! so, asks the user to confirm.
+
+use crate::http::{self, Response};
+use crate::supervisor::ServerSlot;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+const ACTIVITY_PATH: &str = "/api/system/activity";
+const ACTIVITY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Set once the user (or an idle server) has cleared the app to exit.
+static QUIT_CONFIRMED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Activity {
+    streaming_agents: Vec<String>,
+    running_jobs: Vec<String>,
+}
+
+pub fn quit_confirmed() -> bool {
+    QUIT_CONFIRMED.load(Ordering::SeqCst)
+}
+
+/// Exits the app, first asking for confirmation if the server has work in
+/// flight. Runs the activity check off the event loop.
+pub fn request_quit(app: &AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let message = app
+            .try_state::<ServerSlot>()
+            .and_then(|slot| slot.current())
+            .and_then(|s| busy_message(s.port()));
+        let Some(message) = message else {
+            confirm_and_exit(&app);
+            return;
+        };
+        let handle = app.clone();
+        app.dialog()
+            .message(message)
+            .title("Quit Stallion?")
+            .kind(MessageDialogKind::Warning)
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                "Quit".into(),
+                "Keep Running".into(),
+            ))
+            .show(move |quit| {
+                if quit {
+                    confirm_and_exit(&handle);
+                }
+            });
+    });
+}
+
+fn confirm_and_exit(app: &AppHandle) {
+    QUIT_CONFIRMED.store(true, Ordering::SeqCst);
+    app.exit(0);
+}
+
+/// Describes in-flight work, or `None` if the server is idle or unreachable.
+fn busy_message(port: u16) -> Option<String> {
+    let Response::Ok { status: 200, body } = http::get(port, ACTIVITY_PATH, ACTIVITY_TIMEOUT)
+    else {
+        return None;
+    };
+    let activity: Activity = serde_json::from_slice(&body).ok()?;
+
+    let mut parts = Vec::new();
+    if !activity.streaming_agents.is_empty() {
+        parts.push(format!(
+            "{} still responding ({})",
+            plural(activity.streaming_agents.len(), "chat is", "chats are"),
+            activity.streaming_agents.join(", ")
+        ));
+    }
+    if !activity.running_jobs.is_empty() {
+        parts.push(format!(
+            "{} running ({})",
+            plural(activity.running_jobs.len(), "scheduled job is", "scheduled jobs are"),
+            activity.running_jobs.join(", ")
+        ));
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "{}.\n\nQuitting now will interrupt them.",
+        parts.join(" and ")
+    ))
+}
+
+fn plural(n: usize, one: &str, many: &str) -> String {
+    if n == 1 {
+        format!("1 {one}")
+    } else {
+        format!("{n} {many}")
+    }
+}
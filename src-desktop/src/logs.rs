@@ -0,0 +1,469 @@
+//! Persistent, rotating capture of server output under `<data dir>/logs/`.
+//!
+//! The shell pipes the server's stdout and stderr itself and writes every
+//! line to `server.log` with a timestamp and stream tag. The file is rotated
+//! when it grows past the size limit or gets older than the age limit, and
+//! rotated files beyond the retention policy are deleted.
+//!
+//! Every line is also emitted live on `server://log` with a detected level,
+//! and the log files can be tailed or searched through Tauri commands.
+
+use crate::settings::LogSettings;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter, State};
+use tauri_plugin_opener::OpenerExt;
+
+pub const LOG_DIR: &str = "logs";
+pub const LOG_EVENT: &str = "server://log";
+const CURRENT_FILE: &str = "server.log";
+const ROTATED_PREFIX: &str = "server-";
+const DEFAULT_TAIL: usize = 500;
+const DEFAULT_SEARCH_LIMIT: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+    /// Lifecycle notes written by the shell itself.
+    Shell,
+}
+
+impl Stream {
+    fn tag(self) -> &'static str {
+        match self {
+            Stream::Stdout => "out",
+            Stream::Stderr => "err",
+            Stream::Shell => "shell",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "out" => Some(Stream::Stdout),
+            "err" => Some(Stream::Stderr),
+            "shell" => Some(Stream::Shell),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl Level {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "trace" => Some(Level::Trace),
+            "debug" => Some(Level::Debug),
+            "info" => Some(Level::Info),
+            "warn" | "warning" => Some(Level::Warn),
+            "error" | "err" => Some(Level::Error),
+            "fatal" => Some(Level::Fatal),
+            _ => None,
+        }
+    }
+
+    /// Pino's numeric levels.
+    fn from_pino(level: u64) -> Self {
+        match level {
+            0..=10 => Level::Trace,
+            11..=20 => Level::Debug,
+            21..=30 => Level::Info,
+            31..=40 => Level::Warn,
+            41..=50 => Level::Error,
+            _ => Level::Fatal,
+        }
+    }
+
+    /// Pino JSON `level` first, then a level word near the start of the
+    /// line (`INFO:`, `[WARN]`, `Error: ...`), else a per-stream default.
+    pub fn detect(stream: Stream, message: &str) -> Self {
+        let trimmed = message.trim_start();
+        if trimmed.starts_with('{') {
+            if let Ok(json) = serde_json::from_str::<serde_json::Value>(trimmed) {
+                match json.get("level") {
+                    Some(serde_json::Value::Number(n)) => {
+                        if let Some(n) = n.as_u64() {
+                            return Level::from_pino(n);
+                        }
+                    }
+                    Some(serde_json::Value::String(s)) => {
+                        if let Some(level) = Level::from_name(s) {
+                            return level;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let head: String = trimmed.chars().take(80).collect();
+        let mut words = head
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .filter(|w| !w.is_empty());
+        // The first word may be in any case; later ones must be shouted so
+        // prose like "no error found" doesn't count.
+        if let Some(level) = words.next().and_then(Level::from_name) {
+            return level;
+        }
+        if let Some(level) = words
+            .filter(|w| w.chars().all(|c| c.is_ascii_uppercase()))
+            .find_map(Level::from_name)
+        {
+            return level;
+        }
+
+        match stream {
+            Stream::Stderr => Level::Error,
+            Stream::Stdout | Stream::Shell => Level::Info,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLine {
+    pub timestamp: String,
+    pub stream: Stream,
+    pub level: Level,
+    pub message: String,
+}
+
+impl LogLine {
+    /// Parses a line written by [`ServerLog::write_line`].
+    fn parse(entry: &str) -> Option<Self> {
+        let (timestamp, rest) = entry.split_once(' ')?;
+        let rest = rest.strip_prefix('[')?;
+        let (tag, message) = rest.split_once("] ")?;
+        let stream = Stream::from_tag(tag)?;
+        Some(Self {
+            timestamp: timestamp.to_string(),
+            stream,
+            level: Level::detect(stream, message),
+            message: message.to_string(),
+        })
+    }
+}
+
+/// Opens `<data dir>/logs` in the system file manager.
+pub fn open_dir(app: &AppHandle, data_dir: &Path) -> Result<(), String> {
+    let dir = data_dir.join(LOG_DIR);
+    app.opener()
+        .open_path(dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| format!("Failed to open {}: {e}", dir.display()))
+}
+
+pub struct ServerLog {
+    app: AppHandle,
+    dir: PathBuf,
+    settings: LogSettings,
+    current: Mutex<Current>,
+}
+
+struct Current {
+    file: Option<File>,
+    size: u64,
+    opened: SystemTime,
+}
+
+impl ServerLog {
+    pub fn open(app: AppHandle, data_dir: &Path, settings: LogSettings) -> Self {
+        let dir = data_dir.join(LOG_DIR);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            eprintln!("Failed to create log dir {}: {e}", dir.display());
+        }
+        let log = Self {
+            app,
+            current: Mutex::new(open_current(&dir.join(CURRENT_FILE))),
+            dir,
+            settings,
+        };
+        prune(&log.dir, &log.settings);
+        log
+    }
+
+    fn current_path(&self) -> PathBuf {
+        self.dir.join(CURRENT_FILE)
+    }
+
+    pub fn write_line(&self, stream: Stream, line: &str) {
+        let now = Utc::now();
+        let line = LogLine {
+            timestamp: now.to_rfc3339_opts(SecondsFormat::Millis, true),
+            stream,
+            level: Level::detect(stream, line),
+            message: line.trim_end_matches(['\r', '\n']).to_string(),
+        };
+        let entry = format!("{} [{}] {}\n", line.timestamp, stream.tag(), line.message);
+
+        {
+            let mut current = self.current.lock().unwrap();
+            if needs_rotation(&self.settings, &current, entry.len() as u64) {
+                *current = self.rotate(now);
+            }
+            if let Some(file) = current.file.as_mut() {
+                if file.write_all(entry.as_bytes()).is_ok() {
+                    current.size += entry.len() as u64;
+                }
+            }
+        }
+        let _ = self.app.emit(LOG_EVENT, line);
+    }
+
+    /// The last `count` lines across the current and rotated files, oldest
+    /// first.
+    pub fn tail(&self, count: usize) -> Vec<LogLine> {
+        let mut lines = Vec::new();
+        for path in log_files(&self.dir) {
+            let Ok(contents) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            lines.extend(contents.lines().rev().filter_map(LogLine::parse).take(count - lines.len()));
+            if lines.len() >= count {
+                break;
+            }
+        }
+        lines.reverse();
+        lines
+    }
+
+    /// The most recent `limit` lines matching every given filter, oldest
+    /// first. `query` is a case-insensitive substring; `level` is a minimum.
+    pub fn search(&self, filter: &SearchFilter, limit: usize) -> Vec<LogLine> {
+        let query = filter.query.to_lowercase();
+        let mut matches = Vec::new();
+        for path in log_files(&self.dir) {
+            let Ok(contents) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let found = contents
+                .lines()
+                .rev()
+                .filter_map(LogLine::parse)
+                .filter(|line| {
+                    filter.level.is_none_or(|min| line.level >= min)
+                        && filter.stream.is_none_or(|s| line.stream == s)
+                        && (query.is_empty() || line.message.to_lowercase().contains(&query))
+                })
+                .take(limit - matches.len());
+            matches.extend(found);
+            if matches.len() >= limit {
+                break;
+            }
+        }
+        matches.reverse();
+        matches
+    }
+
+    /// Moves `server.log` aside as `server-<timestamp>.log`, prunes old
+    /// files and starts a fresh current file.
+    fn rotate(&self, now: DateTime<Utc>) -> Current {
+        let current = self.current_path();
+        let rotated = self.dir.join(format!(
+            "{ROTATED_PREFIX}{}.log",
+            now.format("%Y%m%dT%H%M%S%.3fZ")
+        ));
+        if let Err(e) = std::fs::rename(&current, &rotated) {
+            eprintln!("Failed to rotate {}: {e}", current.display());
+        }
+        prune(&self.dir, &self.settings);
+        open_current(&current)
+    }
+}
+
+fn needs_rotation(settings: &LogSettings, current: &Current, incoming: u64) -> bool {
+    let max_age = Duration::from_secs(settings.max_age_hours * 3600);
+    current.size > 0
+        && (current.size + incoming > settings.max_file_bytes
+            || current.opened.elapsed().unwrap_or_default() > max_age)
+}
+
+/// Keeps at most `max_files` rotated logs in `dir`, none older than the
+/// retention period.
+fn prune(dir: &Path, settings: &LogSettings) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut rotated: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| is_rotated(p))
+        .collect();
+    // Timestamped names sort chronologically; newest first.
+    rotated.sort_by(|a, b| b.cmp(a));
+
+    let retention = Duration::from_secs(settings.retention_days * 86_400);
+    for (i, path) in rotated.iter().enumerate() {
+        let expired = std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .is_ok_and(|t| t.elapsed().unwrap_or_default() > retention);
+        if i >= settings.max_files || expired {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchFilter {
+    pub query: String,
+    pub level: Option<Level>,
+    pub stream: Option<Stream>,
+}
+
+#[tauri::command]
+pub async fn tail_server_logs(
+    log: State<'_, Arc<ServerLog>>,
+    lines: Option<usize>,
+) -> Result<Vec<LogLine>, String> {
+    Ok(log.tail(lines.unwrap_or(DEFAULT_TAIL)))
+}
+
+#[tauri::command]
+pub async fn search_server_logs(
+    log: State<'_, Arc<ServerLog>>,
+    filter: SearchFilter,
+    limit: Option<usize>,
+) -> Result<Vec<LogLine>, String> {
+    Ok(log.search(&filter, limit.unwrap_or(DEFAULT_SEARCH_LIMIT)))
+}
+
+/// All log files, current first, then rotated files newest first.
+fn log_files(dir: &Path) -> Vec<PathBuf> {
+    let mut rotated: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|e| e.path()).filter(|p| is_rotated(p)).collect())
+        .unwrap_or_default();
+    rotated.sort_by(|a, b| b.cmp(a));
+    let current = dir.join(CURRENT_FILE);
+    current.exists().then_some(current).into_iter().chain(rotated).collect()
+}
+
+fn is_rotated(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with(ROTATED_PREFIX) && n.ends_with(".log"))
+}
+
+fn open_current(path: &Path) -> Current {
+    let file = OpenOptions::new().create(true).append(true).open(path);
+    if let Err(e) = &file {
+        eprintln!("Failed to open {}: {e}", path.display());
+    }
+    let meta = std::fs::metadata(path).ok();
+    Current {
+        size: meta.as_ref().map_or(0, |m| m.len()),
+        // Age is measured from creation so a log appended to across
+        // launches still rotates daily.
+        opened: meta
+            .and_then(|m| m.created().or_else(|_| m.modified()).ok())
+            .unwrap_or_else(SystemTime::now),
+        file: file.ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("stallion-logs-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn detects_pino_levels() {
+        assert_eq!(Level::detect(Stream::Stdout, r#"{"level":10,"msg":"x"}"#), Level::Trace);
+        assert_eq!(Level::detect(Stream::Stdout, r#"{"level":30,"msg":"x"}"#), Level::Info);
+        assert_eq!(Level::detect(Stream::Stdout, r#"{"level":40,"msg":"x"}"#), Level::Warn);
+        assert_eq!(Level::detect(Stream::Stdout, r#"{"level":50,"msg":"x"}"#), Level::Error);
+        assert_eq!(Level::detect(Stream::Stdout, r#"{"level":60,"msg":"x"}"#), Level::Fatal);
+        assert_eq!(Level::detect(Stream::Stdout, r#"{"level":"warning"}"#), Level::Warn);
+    }
+
+    #[test]
+    fn detects_level_words() {
+        assert_eq!(Level::detect(Stream::Stdout, "INFO: listening on 3142"), Level::Info);
+        assert_eq!(Level::detect(Stream::Stdout, "[WARN] slow request"), Level::Warn);
+        assert_eq!(Level::detect(Stream::Stdout, "Error: ENOENT"), Level::Error);
+        assert_eq!(Level::detect(Stream::Stdout, "12:00:01 ERROR boom"), Level::Error);
+        assert_eq!(Level::detect(Stream::Stdout, "  debug cache miss"), Level::Debug);
+    }
+
+    #[test]
+    fn prose_falls_back_to_stream_default() {
+        assert_eq!(Level::detect(Stream::Stdout, "sync done, no error found"), Level::Info);
+        assert_eq!(Level::detect(Stream::Stderr, "something went sideways"), Level::Error);
+        assert_eq!(Level::detect(Stream::Shell, "Server started"), Level::Info);
+        assert_eq!(Level::detect(Stream::Stderr, "{not json"), Level::Error);
+    }
+
+    #[test]
+    fn parses_written_lines() {
+        let line = LogLine::parse("2024-05-01T10:00:00.000Z [err] WARN disk low").unwrap();
+        assert_eq!(line.stream, Stream::Stderr);
+        assert_eq!(line.level, Level::Warn);
+        assert_eq!(line.message, "WARN disk low");
+        assert!(LogLine::parse("garbage").is_none());
+        assert!(LogLine::parse("2024-05-01T10:00:00.000Z [nope] x").is_none());
+    }
+
+    #[test]
+    fn rotates_past_size_or_age() {
+        let settings = LogSettings { max_file_bytes: 100, max_age_hours: 1, ..LogSettings::default() };
+        let fresh = |size| Current { file: None, size, opened: SystemTime::now() };
+        assert!(!needs_rotation(&settings, &fresh(0), 500), "an empty file is never rotated");
+        assert!(!needs_rotation(&settings, &fresh(50), 50));
+        assert!(needs_rotation(&settings, &fresh(50), 51));
+
+        let old = Current { file: None, size: 1, opened: SystemTime::now() - Duration::from_secs(3601) };
+        assert!(needs_rotation(&settings, &old, 1));
+    }
+
+    #[test]
+    fn prune_keeps_newest_files_within_retention() {
+        let dir = temp_dir("prune");
+        let names = ["server-20240101T000000.000Z.log", "server-20240102T000000.000Z.log", "server-20240103T000000.000Z.log"];
+        for name in names {
+            std::fs::write(dir.join(name), "x").unwrap();
+        }
+        std::fs::write(dir.join(CURRENT_FILE), "x").unwrap();
+        std::fs::write(dir.join("notes.txt"), "x").unwrap();
+
+        let settings = LogSettings { max_files: 2, retention_days: 14, ..LogSettings::default() };
+        prune(&dir, &settings);
+        assert!(!dir.join(names[0]).exists());
+        assert!(dir.join(names[1]).exists());
+        assert!(dir.join(names[2]).exists());
+        assert!(dir.join(CURRENT_FILE).exists());
+        assert!(dir.join("notes.txt").exists());
+
+        // Expired by age even though within the count.
+        File::options()
+            .write(true)
+            .open(dir.join(names[1]))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(15 * 86_400))
+            .unwrap();
+        prune(&dir, &settings);
+        assert!(!dir.join(names[1]).exists());
+        assert!(dir.join(names[2]).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,623 @@
+//! Node runtime discovery.
+//!
+//! Candidates are collected in priority order:
+//!
+//! 1. the `STALLION_NODE` override, then the `runtime.nodePath` setting;
+//! 2. the Node sidecar shipped next to the app executable (unless
+//!    `runtime.bundledNode` moves it last or disables it);
+//! 3. installs managed by mise, fnm, asdf, nodenv, nvm and volta (newest
+//!    version first), the login shell `PATH`, and well-known system
+//!    locations.
+//!
+//! Each candidate that exists is asked for its `--version`; the first one at
+//! least as new as the project's minimum (embedded at build time from
+//! `package.json` engines or `.nvmrc`) wins, and the resolution records why.
+
+use crate::settings::{BundledNode, RuntimeSettings};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+
+pub const OVERRIDE_VAR: &str = "STALLION_NODE";
+const NODE_BIN: &str = if cfg!(windows) { "node.exe" } else { "node" };
+const MIN_VERSION: &str = env!("STALLION_MIN_NODE");
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    /// Parses `20`, `v20.11`, `20.11.1` and the like; anything after the
+    /// numeric components (`-rc.1`, `+build`) is ignored.
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim().trim_start_matches('v');
+        let mut parts = raw
+            .split(['.', '-', '+'])
+            .map_while(|p| p.parse::<u32>().ok());
+        Some(Self {
+            major: parts.next()?,
+            minor: parts.next().unwrap_or(0),
+            patch: parts.next().unwrap_or(0),
+        })
+    }
+
+    /// The oldest Node the server supports.
+    pub fn minimum() -> Self {
+        Self::parse(MIN_VERSION).expect("STALLION_MIN_NODE is not a version")
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Source {
+    EnvOverride,
+    Setting,
+    /// The sidecar shipped in the app bundle.
+    Bundled,
+    Mise,
+    Fnm,
+    Asdf,
+    Nodenv,
+    Nvm,
+    Volta,
+    LoginPath,
+    System,
+    /// Nothing found; rely on the OS resolving a bare `node`.
+    Fallback,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Source::EnvOverride => OVERRIDE_VAR,
+            Source::Setting => "desktop setting runtime.nodePath",
+            Source::Bundled => "bundled runtime",
+            Source::Mise => "mise",
+            Source::Fnm => "fnm",
+            Source::Asdf => "asdf",
+            Source::Nodenv => "nodenv",
+            Source::Nvm => "nvm",
+            Source::Volta => "volta",
+            Source::LoginPath => "login shell PATH",
+            Source::System => "system location",
+            Source::Fallback => "bare `node` fallback",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub source: Source,
+    /// Reported by `--version` once probed; before that, the version
+    /// implied by the install directory, if any.
+    pub version: Option<Version>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resolution {
+    pub candidate: Candidate,
+    pub reason: String,
+}
+
+/// Why no candidate could be used: every runtime that was found, with the
+/// version it reported (`None` if it couldn't be run).
+#[derive(Debug, Clone)]
+pub struct NoUsableNode {
+    pub required: Version,
+    pub found: Vec<Candidate>,
+}
+
+impl fmt::Display for NoUsableNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Stallion needs Node.js {} or newer", self.required)?;
+        if self.found.is_empty() {
+            return write!(f, ", and no Node.js installation was found.");
+        }
+        writeln!(f, ". Found:")?;
+        for candidate in &self.found {
+            let version = candidate
+                .version
+                .map_or_else(|| "version unknown".to_string(), |v| v.to_string());
+            write!(
+                f,
+                "\n  {}  ({version}, {})",
+                candidate.path.display(),
+                candidate.source
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub struct NodeResolver {
+    home: PathBuf,
+    override_path: Option<String>,
+    setting: Option<String>,
+    bundled: BundledNode,
+    /// The login shell environment, for `PATH` and version-manager homes.
+    env: BTreeMap<String, String>,
+}
+
+impl NodeResolver {
+    pub fn new(home: &Path, settings: &RuntimeSettings, env: &BTreeMap<String, String>) -> Self {
+        Self {
+            home: home.to_path_buf(),
+            override_path: std::env::var(OVERRIDE_VAR)
+                .ok()
+                .or_else(|| env.get(OVERRIDE_VAR).cloned())
+                .filter(|v| !v.is_empty()),
+            setting: settings.node_path.clone().filter(|v| !v.is_empty()),
+            bundled: settings.bundled_node,
+            env: env.clone(),
+        }
+    }
+
+    /// The first candidate whose `--version` meets the minimum. Candidates
+    /// that are missing are skipped silently; ones that are too old or fail
+    /// to run are reported in the error if nothing better turns up.
+    pub fn resolve(&self) -> Result<Resolution, NoUsableNode> {
+        let required = Version::minimum();
+        let fallback = Candidate {
+            path: PathBuf::from(NODE_BIN),
+            source: Source::Fallback,
+            version: None,
+        };
+        let mut probed: Vec<PathBuf> = Vec::new();
+        let mut found = Vec::new();
+
+        for mut candidate in self.candidates().into_iter().chain([fallback]) {
+            let bare = candidate.source == Source::Fallback;
+            if !bare && !is_executable(&candidate.path) {
+                if matches!(candidate.source, Source::EnvOverride | Source::Setting) {
+                    eprintln!(
+                        "Ignoring {} {}: not an executable file",
+                        candidate.source,
+                        candidate.path.display()
+                    );
+                }
+                continue;
+            }
+            // Version-manager shims and PATH entries often point at the same
+            // binary; ask each one only once.
+            let real = if bare {
+                match on_app_path() {
+                    Some(path) => path,
+                    None => break,
+                }
+            } else {
+                std::fs::canonicalize(&candidate.path).unwrap_or(candidate.path.clone())
+            };
+            if probed.contains(&real) {
+                continue;
+            }
+            probed.push(real);
+
+            let version = match probe_version(&candidate.path) {
+                Ok(version) => version,
+                // A bare `node` that can't be spawned simply isn't installed.
+                Err(_) if bare => break,
+                Err(e) => {
+                    eprintln!("Ignoring {} {}: {e}", candidate.source, candidate.path.display());
+                    None
+                }
+            };
+            candidate.version = version;
+            if version.is_some_and(|v| v >= required) {
+                let reason = describe(&candidate);
+                return Ok(Resolution { candidate, reason });
+            }
+            if let Some(version) = version {
+                eprintln!(
+                    "Skipping {} {}: {version} is older than the required {required}",
+                    candidate.source,
+                    candidate.path.display()
+                );
+            }
+            found.push(candidate);
+        }
+        Err(NoUsableNode { required, found })
+    }
+
+    /// Every candidate in priority order, whether or not it exists.
+    pub fn candidates(&self) -> Vec<Candidate> {
+        let mut out = Vec::new();
+        let explicit = |path: &String, source| Candidate {
+            path: PathBuf::from(path),
+            source,
+            version: None,
+        };
+        out.extend(self.override_path.iter().map(|p| explicit(p, Source::EnvOverride)));
+        out.extend(self.setting.iter().map(|p| explicit(p, Source::Setting)));
+        let bundled = bundled_candidate();
+        if self.bundled == BundledNode::First {
+            out.extend(bundled.clone());
+        }
+
+        let mise = self.dir_from_env("MISE_DATA_DIR").unwrap_or_else(|| self.home.join(".local/share/mise"));
+        out.extend(versioned(&mise.join("installs/node"), "bin", Source::Mise));
+
+        for fnm in self.fnm_dirs() {
+            out.push(Candidate {
+                path: fnm.join("aliases/default/bin").join(NODE_BIN),
+                source: Source::Fnm,
+                version: None,
+            });
+            out.extend(versioned(&fnm.join("node-versions"), "installation/bin", Source::Fnm));
+        }
+
+        let asdf = self.dir_from_env("ASDF_DATA_DIR").unwrap_or_else(|| self.home.join(".asdf"));
+        out.extend(versioned(&asdf.join("installs/nodejs"), "bin", Source::Asdf));
+
+        let nodenv = self.dir_from_env("NODENV_ROOT").unwrap_or_else(|| self.home.join(".nodenv"));
+        out.extend(versioned(&nodenv.join("versions"), "bin", Source::Nodenv));
+
+        out.extend(self.nvm_candidates());
+
+        out.push(Candidate {
+            path: self.home.join(".volta/bin").join(NODE_BIN),
+            source: Source::Volta,
+            version: None,
+        });
+
+        let login_path = self.env.get("PATH").map(String::as_str).unwrap_or_default();
+        out.extend(std::env::split_paths(login_path).map(|dir| Candidate {
+            path: dir.join(NODE_BIN),
+            source: Source::LoginPath,
+            version: None,
+        }));
+
+        out.extend(
+            ["/opt/homebrew/bin", "/usr/local/bin", "/usr/bin"].iter().map(|dir| Candidate {
+                path: Path::new(dir).join(NODE_BIN),
+                source: Source::System,
+                version: None,
+            }),
+        );
+        if self.bundled == BundledNode::Last {
+            out.extend(bundled);
+        }
+        out
+    }
+
+    /// A directory variable from the login environment, falling back to the
+    /// app's own.
+    fn dir_from_env(&self, var: &str) -> Option<PathBuf> {
+        self.env
+            .get(var)
+            .cloned()
+            .or_else(|| std::env::var(var).ok())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    }
+
+    fn fnm_dirs(&self) -> Vec<PathBuf> {
+        if let Some(dir) = self.dir_from_env("FNM_DIR") {
+            return vec![dir];
+        }
+        vec![
+            self.home.join(".local/share/fnm"),
+            self.home.join("Library/Application Support/fnm"),
+            self.home.join(".fnm"),
+        ]
+    }
+
+    /// Real nvm layout: `versions/node/v*/bin/node`, with installs matching
+    /// the `default` alias first, then the legacy `current` symlink.
+    fn nvm_candidates(&self) -> Vec<Candidate> {
+        let nvm = self.dir_from_env("NVM_DIR").unwrap_or_else(|| self.home.join(".nvm"));
+        let mut installs = versioned(&nvm.join("versions/node"), "bin", Source::Nvm);
+
+        let default_alias = std::fs::read_to_string(nvm.join("alias/default")).ok();
+        if let Some(alias) = default_alias.as_deref().map(str::trim).filter(|a| !a.is_empty()) {
+            let alias = alias.trim_start_matches('v');
+            let matches_alias = |c: &Candidate| {
+                c.version.is_some_and(|v| {
+                    let full = format!("{}.{}.{}", v.major, v.minor, v.patch);
+                    full == alias || full.starts_with(&format!("{alias}."))
+                })
+            };
+            // Stable sort keeps newest-first order within each group.
+            installs.sort_by_key(|c| !matches_alias(c));
+        }
+
+        installs.push(Candidate {
+            path: nvm.join("current/bin").join(NODE_BIN),
+            source: Source::Nvm,
+            version: None,
+        });
+        installs
+    }
+}
+
+/// Tauri installs external binaries next to the app executable with the
+/// target triple stripped, e.g. `Stallion.app/Contents/MacOS/node`.
+fn bundled_candidate() -> Option<Candidate> {
+    let exe = std::env::current_exe().ok()?;
+    Some(Candidate {
+        path: exe.parent()?.join(NODE_BIN),
+        source: Source::Bundled,
+        version: None,
+    })
+}
+
+/// `<root>/<version>/<bin_dir>/node` for every version directory under
+/// `root`, newest version first. Directories that aren't versions
+/// (`latest`, `lts`) are skipped.
+fn versioned(root: &Path, bin_dir: &str, source: Source) -> Vec<Candidate> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut found: Vec<Candidate> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let version = Version::parse(entry.file_name().to_str()?)?;
+            Some(Candidate {
+                path: entry.path().join(bin_dir).join(NODE_BIN),
+                source,
+                version: Some(version),
+            })
+        })
+        .collect();
+    found.sort_by_key(|c| Reverse(c.version));
+    found
+}
+
+fn describe(candidate: &Candidate) -> String {
+    match candidate.source {
+        Source::EnvOverride | Source::Setting => {
+            format!("explicitly configured via {}", candidate.source)
+        }
+        Source::Bundled => "shipped with the app".into(),
+        Source::LoginPath => "first compatible `node` on the login shell PATH".into(),
+        Source::System => "found in a system location".into(),
+        Source::Fallback => "bare `node` resolved by the OS".into(),
+        source => format!("newest compatible {source} install"),
+    }
+}
+
+/// Runs `<path> --version`, giving up after a few seconds in case a shim
+/// decides to download or prompt. `Ok(None)` means it ran but printed
+/// something that isn't a version.
+fn probe_version(path: &Path) -> Result<Option<Version>, String> {
+    let mut child = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("failed to run: {e}"))?;
+
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if start.elapsed() < VERSION_TIMEOUT => {
+                std::thread::sleep(Duration::from_millis(20))
+            }
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err("timed out reporting its version".into());
+            }
+            Err(e) => return Err(format!("failed to run: {e}")),
+        }
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("failed to read its version: {e}"))?;
+    Ok(Version::parse(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+impl NoUsableNode {
+    /// What the user can do about it, shown under the error on the splash
+    /// or in [`report_unusable`].
+    pub fn advice(&self) -> String {
+        format!(
+            "Install Node.js {} or newer (for example with nvm, fnm or mise), \
+             or point Stallion at one by setting runtime.nodePath in \
+             ~/.stallion-ai/desktop.json or the {OVERRIDE_VAR} environment variable, \
+             then retry.",
+            self.required
+        )
+    }
+}
+
+/// Explains in a native dialog why no Node could be used, for when there is
+/// no splash to show it (it failed to open, or the server was restarted
+/// after the hand-off).
+pub fn report_unusable(app: &AppHandle, error: &NoUsableNode) {
+    app.dialog()
+        .message(format!("{error}\n\n{}", error.advice()))
+        .title("Node.js not found")
+        .kind(MessageDialogKind::Error)
+        .show(|_| {});
+}
+
+/// Where the OS would find a bare `node` using the app's own `PATH`.
+fn on_app_path() -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(NODE_BIN))
+        .find(|p| is_executable(p))
+        .map(|p| std::fs::canonicalize(&p).unwrap_or(p))
+}
+
+/// A regular file with an execute bit set (on Windows, any regular file).
+pub fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_home(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("stallion-node-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn mkdirs(root: &Path, dirs: &[&str]) {
+        for dir in dirs {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+    }
+
+    /// A resolver that only looks inside `home`, whatever the test
+    /// process's own environment holds.
+    fn resolver(home: &Path, bundled: BundledNode) -> NodeResolver {
+        let env = BTreeMap::from([
+            ("PATH".to_string(), home.join("path-bin").display().to_string()),
+            ("MISE_DATA_DIR".to_string(), home.join("mise").display().to_string()),
+            ("FNM_DIR".to_string(), home.join("fnm").display().to_string()),
+            ("ASDF_DATA_DIR".to_string(), home.join("asdf").display().to_string()),
+            ("NODENV_ROOT".to_string(), home.join("nodenv").display().to_string()),
+            ("NVM_DIR".to_string(), home.join("nvm").display().to_string()),
+        ]);
+        NodeResolver {
+            home: home.to_path_buf(),
+            override_path: None,
+            setting: Some("/opt/custom/node".into()),
+            bundled,
+            env,
+        }
+    }
+
+    fn versions(candidates: &[Candidate]) -> Vec<String> {
+        candidates
+            .iter()
+            .map(|c| c.version.map_or_else(|| "-".into(), |v| v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_version_forms() {
+        let v = |major, minor, patch| Some(Version { major, minor, patch });
+        assert_eq!(Version::parse("20"), v(20, 0, 0));
+        assert_eq!(Version::parse("v20.11"), v(20, 11, 0));
+        assert_eq!(Version::parse("20.11.1"), v(20, 11, 1));
+        assert_eq!(Version::parse(" v22.0.0-rc.1\n"), v(22, 0, 0));
+        assert_eq!(Version::parse("18.19.0+build.7"), v(18, 19, 0));
+        assert_eq!(Version::parse("lts"), None);
+        assert_eq!(Version::parse(""), None);
+    }
+
+    #[test]
+    fn orders_versions_numerically() {
+        let v = |raw| Version::parse(raw).unwrap();
+        assert!(v("9.11.2") < v("20.0.0"));
+        assert!(v("20.9.0") < v("20.10.0"));
+        assert!(v("20.10.9") < v("20.10.10"));
+        assert!(v("v20") == v("20.0.0"));
+        assert!(v("18.20.0") < v("20"));
+        assert_eq!(v("20.11.1").to_string(), "v20.11.1");
+    }
+
+    #[test]
+    fn explains_what_was_found() {
+        let none = NoUsableNode { required: Version::parse("20").unwrap(), found: Vec::new() };
+        assert_eq!(none.to_string(), "Stallion needs Node.js v20.0.0 or newer, and no Node.js installation was found.");
+
+        let old = NoUsableNode {
+            required: Version::parse("20").unwrap(),
+            found: vec![Candidate {
+                path: PathBuf::from("/usr/bin/node"),
+                source: Source::System,
+                version: Version::parse("18.19.0"),
+            }],
+        };
+        assert!(old.to_string().contains("/usr/bin/node  (v18.19.0, system location)"));
+    }
+
+    #[test]
+    fn versioned_sorts_numerically_newest_first() {
+        let home = temp_home("versioned");
+        let root = home.join("installs");
+        mkdirs(&root, &["9.11.2", "20.11.1", "18.0.0", "v20.2.0", "latest", "lts"]);
+
+        let found = versioned(&root, "bin", Source::Mise);
+        assert_eq!(versions(&found), ["v20.11.1", "v20.2.0", "v18.0.0", "v9.11.2"]);
+        assert_eq!(found[0].path, root.join("20.11.1/bin").join(NODE_BIN));
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn nvm_default_alias_comes_first() {
+        let home = temp_home("nvm");
+        let nvm = home.join("nvm");
+        mkdirs(&nvm, &["versions/node/v18.19.0", "versions/node/v22.1.0", "versions/node/v20.10.0", "alias"]);
+        std::fs::write(nvm.join("alias/default"), "18\n").unwrap();
+
+        let found = resolver(&home, BundledNode::Never).nvm_candidates();
+        assert_eq!(versions(&found), ["v18.19.0", "v22.1.0", "v20.10.0", "-"]);
+        assert_eq!(found[3].path, nvm.join("current/bin").join(NODE_BIN));
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn candidates_follow_priority_order() {
+        let home = temp_home("order");
+        mkdirs(
+            &home,
+            &[
+                "mise/installs/node/20.0.0",
+                "fnm/node-versions/v21.0.0",
+                "asdf/installs/nodejs/19.0.0",
+                "nodenv/versions/18.0.0",
+                "nvm/versions/node/v22.0.0",
+            ],
+        );
+
+        let sources = |bundled| {
+            let mut order: Vec<Source> = Vec::new();
+            for c in resolver(&home, bundled).candidates() {
+                if order.last() != Some(&c.source) {
+                    order.push(c.source);
+                }
+            }
+            order
+        };
+        let managed = [
+            Source::Mise,
+            Source::Fnm,
+            Source::Asdf,
+            Source::Nodenv,
+            Source::Nvm,
+            Source::Volta,
+            Source::LoginPath,
+            Source::System,
+        ];
+        assert_eq!(sources(BundledNode::First), [&[Source::Setting, Source::Bundled][..], &managed].concat());
+        assert_eq!(sources(BundledNode::Last), [&[Source::Setting][..], &managed, &[Source::Bundled]].concat());
+        assert_eq!(sources(BundledNode::Never), [&[Source::Setting][..], &managed].concat());
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+}
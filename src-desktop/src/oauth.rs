@@ -0,0 +1,643 @@
+//! Native OAuth 2.0 authorization code flow with PKCE, for plugins.
+//!
+//! `start_oauth_flow` opens the system browser at the authorization URL and
+//! waits for the redirect on a one-shot listener bound to 127.0.0.1, the
+//! loopback redirect of RFC 8252. The redirect must carry the `state` that
+//! was sent, and its code is exchanged together with the PKCE verifier
+//! (RFC 7636, S256), so the webview never sees the code and a stolen code is
+//! useless. The tokens go back to the caller or straight to a path on the
+//! local server.
+//!
+//! Endpoints must use HTTPS unless they are on this machine, which is what
+//! lets a flow run against `scripts/mock-oauth-server.mjs`.
+
+use crate::error::{serialize_command_error, CommandError};
+use crate::http::{self, Response};
+use crate::startup::Startup;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, State};
+use tauri_plugin_opener::OpenerExt;
+use url::{Host, Url};
+
+const DEFAULT_REDIRECT_PATH: &str = "/callback";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long a browser that connected gets to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST_BYTES: usize = 16 * 1024;
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Authorization parameters the flow sets itself.
+const RESERVED_PARAMS: &[&str] = &[
+    "response_type",
+    "client_id",
+    "redirect_uri",
+    "scope",
+    "state",
+    "code_challenge",
+    "code_challenge_method",
+];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OAuthRequest {
+    authorize_url: String,
+    token_url: String,
+    client_id: String,
+    /// Only for providers that want one even with PKCE.
+    client_secret: Option<String>,
+    #[serde(default)]
+    scopes: Vec<String>,
+    /// Added to the authorization URL, e.g. `audience` or `prompt`.
+    #[serde(default)]
+    extra_params: BTreeMap<String, String>,
+    /// For providers that register the exact redirect URI; any free port is
+    /// used otherwise.
+    redirect_port: Option<u16>,
+    redirect_path: Option<String>,
+    /// How long to wait for the user to finish in the browser.
+    timeout_ms: Option<u64>,
+    #[serde(default)]
+    deliver: Delivery,
+}
+
+/// Where the tokens go.
+#[derive(Default, Deserialize)]
+#[serde(tag = "to", rename_all = "camelCase")]
+pub enum Delivery {
+    /// Returned from `start_oauth_flow`.
+    #[default]
+    Caller,
+    /// POSTed as JSON to this path on the local server, and not returned.
+    Server { path: String },
+}
+
+#[derive(Debug)]
+pub enum OAuthError {
+    InvalidRequest(String),
+    /// Another flow is waiting for its redirect.
+    Busy,
+    Listener(String),
+    Browser(String),
+    Timeout { after: Duration },
+    Cancelled,
+    /// The authorization server sent the user back with an error.
+    Denied { error: String, description: Option<String> },
+    /// The redirect's `state` isn't the one that was sent.
+    StateMismatch,
+    Exchange(String),
+    Delivery(String),
+}
+
+impl CommandError for OAuthError {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest(_) => "invalidRequest",
+            Self::Busy => "busy",
+            Self::Listener(_) => "listener",
+            Self::Browser(_) => "browser",
+            Self::Timeout { .. } => "timeout",
+            Self::Cancelled => "cancelled",
+            Self::Denied { .. } => "denied",
+            Self::StateMismatch => "stateMismatch",
+            Self::Exchange(_) => "exchange",
+            Self::Delivery(_) => "delivery",
+        }
+    }
+}
+
+impl fmt::Display for OAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidRequest(message) => write!(f, "Invalid OAuth request: {message}"),
+            Self::Busy => write!(f, "Another sign-in is already waiting in the browser"),
+            Self::Listener(message) | Self::Browser(message) | Self::Delivery(message) => {
+                write!(f, "{message}")
+            }
+            Self::Timeout { after } if after.as_secs() == 0 => {
+                write!(f, "Sign-in timed out after {}ms", after.as_millis())
+            }
+            Self::Timeout { after } => {
+                write!(f, "Sign-in timed out after {}s", after.as_secs())
+            }
+            Self::Cancelled => write!(f, "Sign-in was cancelled"),
+            Self::Denied { error, description } => {
+                write!(f, "Sign-in was refused ({error})")?;
+                if let Some(description) = description {
+                    write!(f, ": {description}")?;
+                }
+                Ok(())
+            }
+            Self::StateMismatch => write!(f, "The sign-in redirect didn't match this request"),
+            Self::Exchange(message) => write!(f, "Token exchange failed: {message}"),
+        }
+    }
+}
+
+serialize_command_error!(OAuthError);
+
+/// The cancel flag of the flow waiting for its redirect, if any.
+#[derive(Default)]
+pub struct OAuthFlows(Mutex<Option<Arc<AtomicBool>>>);
+
+impl OAuthFlows {
+    fn begin(&self) -> Result<FlowGuard<'_>, OAuthError> {
+        let mut current = self.0.lock().unwrap();
+        if current.is_some() {
+            return Err(OAuthError::Busy);
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        *current = Some(cancel.clone());
+        Ok(FlowGuard { flows: self, cancel })
+    }
+
+    fn cancel(&self) -> bool {
+        match self.0.lock().unwrap().as_ref() {
+            Some(cancel) => {
+                cancel.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Clears the flow when it ends, however it ends.
+struct FlowGuard<'a> {
+    flows: &'a OAuthFlows,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Drop for FlowGuard<'_> {
+    fn drop(&mut self) {
+        self.flows.0.lock().unwrap().take();
+    }
+}
+
+/// A validated request with its PKCE pair, `state` and bound listener.
+struct Flow {
+    authorize_url: Url,
+    token_url: Url,
+    client_id: String,
+    client_secret: Option<String>,
+    state: String,
+    verifier: String,
+    redirect: Redirect,
+    timeout: Duration,
+}
+
+impl Flow {
+    fn prepare(request: &OAuthRequest) -> Result<Self, OAuthError> {
+        let mut authorize_url = endpoint("authorizeUrl", &request.authorize_url)?;
+        let token_url = endpoint("tokenUrl", &request.token_url)?;
+        if request.client_id.is_empty() {
+            return Err(OAuthError::InvalidRequest("clientId is required".into()));
+        }
+        if let Some(name) = request.extra_params.keys().find(|k| RESERVED_PARAMS.contains(&k.as_str())) {
+            return Err(OAuthError::InvalidRequest(format!("{name} is set by the flow itself")));
+        }
+        let path = request.redirect_path.as_deref().unwrap_or(DEFAULT_REDIRECT_PATH);
+        if !path.starts_with('/') {
+            return Err(OAuthError::InvalidRequest(format!("redirectPath {path:?} must start with '/'")));
+        }
+
+        let state = URL_SAFE_NO_PAD.encode(random_bytes::<16>()?);
+        let verifier = URL_SAFE_NO_PAD.encode(random_bytes::<32>()?);
+        let challenge = challenge(&verifier);
+        let redirect = Redirect::bind(request.redirect_port.unwrap_or(0), path)?;
+
+        {
+            let mut query = authorize_url.query_pairs_mut();
+            query
+                .append_pair("response_type", "code")
+                .append_pair("client_id", &request.client_id)
+                .append_pair("redirect_uri", &redirect.uri);
+            if !request.scopes.is_empty() {
+                query.append_pair("scope", &request.scopes.join(" "));
+            }
+            query
+                .append_pair("state", &state)
+                .append_pair("code_challenge", &challenge)
+                .append_pair("code_challenge_method", "S256");
+            for (name, value) in &request.extra_params {
+                query.append_pair(name, value);
+            }
+        }
+
+        Ok(Self {
+            authorize_url,
+            token_url,
+            client_id: request.client_id.clone(),
+            client_secret: request.client_secret.clone(),
+            state,
+            verifier,
+            redirect,
+            timeout: request.timeout_ms.map_or(DEFAULT_TIMEOUT, Duration::from_millis),
+        })
+    }
+
+    /// Waits for the redirect and returns its authorization code. Blocks, so
+    /// call it off the async runtime.
+    fn wait(&self, cancel: &AtomicBool) -> Result<String, OAuthError> {
+        self.redirect.wait(&self.state, self.timeout, cancel)
+    }
+
+    /// Trades the code and PKCE verifier for tokens at the token endpoint and
+    /// returns the endpoint's JSON response.
+    async fn exchange(&self, code: &str) -> Result<Value, OAuthError> {
+        let body = {
+            let mut form = url::form_urlencoded::Serializer::new(String::new());
+            form.append_pair("grant_type", "authorization_code")
+                .append_pair("code", code)
+                .append_pair("redirect_uri", &self.redirect.uri)
+                .append_pair("client_id", &self.client_id)
+                .append_pair("code_verifier", &self.verifier);
+            if let Some(secret) = &self.client_secret {
+                form.append_pair("client_secret", secret);
+            }
+            form.finish()
+        };
+        let response = http::client(HTTP_TIMEOUT)
+            .map_err(OAuthError::Exchange)?
+            .post(self.token_url.clone())
+            // GitHub answers with a form-encoded body unless asked for JSON.
+            .header(ACCEPT, "application/json")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| OAuthError::Exchange(e.to_string()))?;
+        let status = response.status();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| OAuthError::Exchange(e.to_string()))?;
+
+        let Ok(tokens) = serde_json::from_slice::<Value>(&body) else {
+            return Err(OAuthError::Exchange(format!("the token endpoint answered {status} without JSON")));
+        };
+        if let Some(error) = tokens.get("error").and_then(Value::as_str) {
+            let description = tokens.get("error_description").and_then(Value::as_str);
+            return Err(OAuthError::Exchange(match description {
+                Some(description) => format!("{error}: {description}"),
+                None => error.to_string(),
+            }));
+        }
+        if !status.is_success() {
+            return Err(OAuthError::Exchange(format!("the token endpoint answered {status}")));
+        }
+        if tokens.get("access_token").and_then(Value::as_str).is_none() {
+            return Err(OAuthError::Exchange("the response has no access_token".into()));
+        }
+        Ok(tokens)
+    }
+}
+
+/// The one-shot loopback listener the browser is sent back to.
+struct Redirect {
+    listener: TcpListener,
+    uri: String,
+    path: String,
+}
+
+impl Redirect {
+    fn bind(port: u16, path: &str) -> Result<Self, OAuthError> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+            .map_err(|e| OAuthError::Listener(format!("Failed to listen on 127.0.0.1:{port}: {e}")))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| OAuthError::Listener(e.to_string()))?
+            .port();
+        Ok(Self {
+            listener,
+            uri: format!("http://127.0.0.1:{port}{path}"),
+            path: path.to_string(),
+        })
+    }
+
+    /// Serves requests until one arrives on the redirect path, and checks
+    /// it. Anything else (a favicon, say) gets a 404 and is ignored.
+    fn wait(&self, state: &str, timeout: Duration, cancel: &AtomicBool) -> Result<String, OAuthError> {
+        let start = Instant::now();
+        loop {
+            if cancel.load(Ordering::SeqCst) {
+                return Err(OAuthError::Cancelled);
+            }
+            if start.elapsed() >= timeout {
+                return Err(OAuthError::Timeout { after: timeout });
+            }
+            let mut stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(POLL_INTERVAL);
+                    continue;
+                }
+                Err(e) => return Err(OAuthError::Listener(format!("Failed to accept the redirect: {e}"))),
+            };
+            let Some(target) = read_target(&mut stream) else {
+                continue;
+            };
+            if target.path() != self.path {
+                respond(&mut stream, "404 Not Found", "Not found.");
+                continue;
+            }
+
+            let params: BTreeMap<String, String> = target.query_pairs().into_owned().collect();
+            let result = check(&params, state);
+            let message = match &result {
+                Ok(_) => "Signed in. You can close this tab and return to Stallion.",
+                Err(_) => "Sign-in failed. You can close this tab and return to Stallion.",
+            };
+            respond(&mut stream, "200 OK", message);
+            return result;
+        }
+    }
+}
+
+/// The request target of an HTTP request, resolved against the listener.
+fn read_target(stream: &mut TcpStream) -> Option<Url> {
+    // Accepted sockets inherit non-blocking mode on some platforms.
+    stream.set_nonblocking(false).ok()?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT)).ok()?;
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_BYTES {
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.lines().next()?.split(' ');
+    let (_method, target) = (parts.next()?, parts.next()?);
+    Url::parse("http://127.0.0.1").ok()?.join(target).ok()
+}
+
+fn check(params: &BTreeMap<String, String>, state: &str) -> Result<String, OAuthError> {
+    if params.get("state").map(String::as_str) != Some(state) {
+        return Err(OAuthError::StateMismatch);
+    }
+    if let Some(error) = params.get("error") {
+        return Err(OAuthError::Denied {
+            error: error.clone(),
+            description: params.get("error_description").cloned(),
+        });
+    }
+    params
+        .get("code")
+        .cloned()
+        .ok_or_else(|| OAuthError::Exchange("the redirect has no code".into()))
+}
+
+/// Answers the browser with a page that says what happened. Nothing from
+/// the request is echoed back.
+fn respond(stream: &mut TcpStream, status: &str, message: &str) {
+    let body = format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\"><title>Stallion</title></head>\
+         <body style=\"font-family: system-ui, sans-serif; text-align: center; padding-top: 4em\">\
+         <p>{message}</p></body></html>"
+    );
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+/// Parses an endpoint URL, which must be HTTPS unless it's on this machine.
+fn endpoint(name: &str, raw: &str) -> Result<Url, OAuthError> {
+    let url = Url::parse(raw).map_err(|e| OAuthError::InvalidRequest(format!("{name} {raw:?}: {e}")))?;
+    let local = match url.host() {
+        Some(Host::Domain(domain)) => domain == "localhost",
+        Some(Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    };
+    match url.scheme() {
+        "https" => Ok(url),
+        "http" if local => Ok(url),
+        _ => Err(OAuthError::InvalidRequest(format!("{name} must use https"))),
+    }
+}
+
+/// The S256 code challenge for a PKCE verifier.
+fn challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], OAuthError> {
+    let mut bytes = [0u8; N];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| OAuthError::InvalidRequest(format!("No randomness for PKCE: {e}")))?;
+    Ok(bytes)
+}
+
+async fn deliver_to_server(startup: &Startup, path: &str, tokens: &Value) -> Result<(), OAuthError> {
+    let port = startup
+        .server_status()
+        .port
+        .ok_or_else(|| OAuthError::Delivery("The server isn't running to receive the tokens".into()))?;
+    let (path, body) = (path.to_string(), tokens.to_string());
+    let response = tauri::async_runtime::spawn_blocking(move || http::post_json(port, &path, body, HTTP_TIMEOUT))
+        .await
+        .map_err(|e| OAuthError::Delivery(e.to_string()))?;
+    match response {
+        Response::Ok { status, .. } if (200..300).contains(&status) => Ok(()),
+        Response::Ok { status, .. } => Err(OAuthError::Delivery(format!("The server refused the tokens ({status})"))),
+        Response::Refused | Response::Failed => {
+            Err(OAuthError::Delivery("Failed to send the tokens to the server".into()))
+        }
+    }
+}
+
+/// Runs an authorization code flow with PKCE in the system browser. Returns
+/// the token endpoint's response, or nothing when the tokens were delivered
+/// to the server instead.
+#[tauri::command]
+pub async fn start_oauth_flow(
+    app: AppHandle,
+    request: OAuthRequest,
+    flows: State<'_, Arc<OAuthFlows>>,
+    startup: State<'_, Arc<Startup>>,
+) -> Result<Option<Value>, OAuthError> {
+    if let Delivery::Server { path } = &request.deliver {
+        if !path.starts_with('/') || path.starts_with("//") {
+            return Err(OAuthError::InvalidRequest(format!("server path {path:?} must start with '/'")));
+        }
+    }
+    let flows = flows.inner().clone();
+    let startup = startup.inner().clone();
+    let run = flows.begin()?;
+    let flow = Arc::new(Flow::prepare(&request)?);
+
+    app.opener()
+        .open_url(flow.authorize_url.as_str(), None::<&str>)
+        .map_err(|e| OAuthError::Browser(format!("Failed to open the browser: {e}")))?;
+    let waiting = flow.clone();
+    let cancel = run.cancel.clone();
+    let code = tauri::async_runtime::spawn_blocking(move || waiting.wait(&cancel))
+        .await
+        .map_err(|e| OAuthError::Listener(e.to_string()))??;
+    let tokens = flow.exchange(&code).await.inspect_err(|e| eprintln!("OAuth flow failed: {e}"))?;
+
+    match &request.deliver {
+        Delivery::Caller => Ok(Some(tokens)),
+        Delivery::Server { path } => {
+            deliver_to_server(&startup, path, &tokens).await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Abandons the flow waiting for its redirect. Returns whether there was one.
+#[tauri::command]
+pub fn cancel_oauth_flow(flows: State<'_, Arc<OAuthFlows>>) -> bool {
+    flows.cancel()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(token_url: &str) -> OAuthRequest {
+        serde_json::from_value(json!({
+            "authorizeUrl": "https://auth.example.com/authorize",
+            "tokenUrl": token_url,
+            "clientId": "stallion-test",
+            "scopes": ["openid", "email"],
+        }))
+        .unwrap()
+    }
+
+    /// Sends `request` to the redirect listener as a browser would.
+    fn visit(redirect: &Redirect, target: &str) -> std::thread::JoinHandle<String> {
+        let port = redirect.listener.local_addr().unwrap().port();
+        let request = format!("GET {target} HTTP/1.1\r\nHost: 127.0.0.1:{port}\r\n\r\n");
+        std::thread::spawn(move || {
+            let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        })
+    }
+
+    /// A token endpoint that answers one request with `answer` and returns
+    /// the form it was sent.
+    fn token_endpoint(answer: Value) -> (String, std::thread::JoinHandle<BTreeMap<String, String>>) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let url = format!("http://127.0.0.1:{}/token", listener.local_addr().unwrap().port());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            let body_at = loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break end + 4;
+                }
+            };
+            let head = String::from_utf8_lossy(&request[..body_at]).to_lowercase();
+            let length: usize = head
+                .lines()
+                .find_map(|l| l.strip_prefix("content-length:"))
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap();
+            while request.len() < body_at + length {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            let body = answer.to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+            url::form_urlencoded::parse(&request[body_at..]).into_owned().collect()
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn derives_the_s256_challenge() {
+        // RFC 7636, appendix B.
+        assert_eq!(
+            challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn authorize_url_carries_the_challenge_and_state() {
+        let flow = Flow::prepare(&request("https://auth.example.com/token")).unwrap();
+        let params: BTreeMap<String, String> = flow.authorize_url.query_pairs().into_owned().collect();
+        assert_eq!(params["code_challenge"], challenge(&flow.verifier));
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert_eq!(params["state"], flow.state);
+        assert_eq!(params["redirect_uri"], flow.redirect.uri);
+        assert_eq!(params["scope"], "openid email");
+        assert_ne!(flow.verifier, Flow::prepare(&request("https://auth.example.com/token")).unwrap().verifier);
+    }
+
+    #[test]
+    fn rejects_a_redirect_with_another_state() {
+        let redirect = Redirect::bind(0, "/callback").unwrap();
+        let browser = visit(&redirect, "/callback?code=stolen&state=forged");
+        let result = redirect.wait("expected", Duration::from_secs(5), &AtomicBool::new(false));
+        assert!(matches!(result, Err(OAuthError::StateMismatch)), "{result:?}");
+        assert!(browser.join().unwrap().contains("Sign-in failed"));
+    }
+
+    #[test]
+    fn ignores_other_paths_until_the_redirect() {
+        let redirect = Redirect::bind(0, "/callback").unwrap();
+        let favicon = visit(&redirect, "/favicon.ico");
+        assert!(favicon.join().unwrap().starts_with("HTTP/1.1 404"));
+        let browser = visit(&redirect, "/callback?code=abc&state=expected");
+        let code = redirect.wait("expected", Duration::from_secs(5), &AtomicBool::new(false)).unwrap();
+        assert_eq!(code, "abc");
+        assert!(browser.join().unwrap().starts_with("HTTP/1.1 200"));
+    }
+
+    #[test]
+    fn exchanges_the_code_for_tokens() {
+        let (token_url, endpoint) = token_endpoint(json!({ "access_token": "at-1", "token_type": "Bearer" }));
+        let flow = Flow::prepare(&request(&token_url)).unwrap();
+        let tokens = tauri::async_runtime::block_on(flow.exchange("abc")).unwrap();
+        assert_eq!(tokens["access_token"], "at-1");
+
+        let form = endpoint.join().unwrap();
+        assert_eq!(form["grant_type"], "authorization_code");
+        assert_eq!(form["code"], "abc");
+        assert_eq!(form["client_id"], "stallion-test");
+        assert_eq!(form["redirect_uri"], flow.redirect.uri);
+        assert_eq!(form["code_verifier"], flow.verifier);
+    }
+
+    #[test]
+    fn reports_a_token_endpoint_error() {
+        let (token_url, endpoint) = token_endpoint(json!({
+            "error": "invalid_grant",
+            "error_description": "code expired",
+        }));
+        let flow = Flow::prepare(&request(&token_url)).unwrap();
+        let error = tauri::async_runtime::block_on(flow.exchange("abc")).unwrap_err();
+        assert_eq!(error.to_string(), OAuthError::Exchange("invalid_grant: code expired".into()).to_string());
+        endpoint.join().unwrap();
+    }
+}
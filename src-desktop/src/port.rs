@@ -0,0 +1,303 @@
+//! Loopback port selection for the server.
+//!
+//! The server listens on `port` for HTTP, `port + 1` for the terminal
+//! WebSocket and `port + 2` for the voice WebSocket, so a candidate is only
+//! usable when the whole block is free.
+//!
+//! When the preferred block is taken, the owning process is identified
+//! before anything is terminated: a stale Stallion server from this data
+//! dir is stopped silently, any other process is only stopped if the user
+//! says so. Otherwise the server simply moves to another port.
+
+use std::net::{Ipv4Addr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+pub const PREFERRED_PORT: u16 = 3142;
+const PORT_BLOCK: u16 = 3;
+const MAX_ATTEMPTS: usize = 20;
+const RELEASE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The process listening on a port, as far as it can be identified.
+#[derive(Debug, Clone)]
+pub struct PortOwner {
+    pub pid: u32,
+    pub command: String,
+    /// `STALLION_AI_DIR` from the owner's environment, if readable.
+    pub data_dir: Option<PathBuf>,
+}
+
+impl PortOwner {
+    /// A server spawned from this app's bundle, or any Stallion server
+    /// pointed at the same data dir.
+    fn is_stale_server(&self, server_script: &Path, data_dir: &Path) -> bool {
+        let script = server_script.to_string_lossy();
+        if self.command.contains(script.as_ref()) {
+            return true;
+        }
+        self.command.contains("dist-server/index.js")
+            && self.data_dir.as_deref() == Some(data_dir)
+    }
+
+    pub fn name(&self) -> String {
+        let program = self.command.split_whitespace().next().unwrap_or("unknown");
+        let program = Path::new(program)
+            .file_name()
+            .map_or(program.into(), |n| n.to_string_lossy());
+        format!("{program} (pid {})", self.pid)
+    }
+}
+
+/// Claims the preferred port block, clearing a stale Stallion server or,
+/// with the user's consent, another process. Falls back to any free block.
+pub fn claim_port(
+    app: &AppHandle,
+    preferred: u16,
+    server_script: &Path,
+    data_dir: &Path,
+) -> Result<u16, String> {
+    if block_is_free(preferred) {
+        return Ok(preferred);
+    }
+
+    let taken = (0..PORT_BLOCK).map(|i| preferred + i).find(|p| !is_free(*p));
+    if let Some(owner) = taken.and_then(find_owner) {
+        let stop = if owner.is_stale_server(server_script, data_dir) {
+            eprintln!("Stopping stale Stallion server {}", owner.name());
+            true
+        } else {
+            confirm_stop(app, &owner, preferred)
+        };
+        if stop && terminate(owner.pid) && wait_until_free(preferred) {
+            return Ok(preferred);
+        }
+    }
+
+    let port = pick_port(preferred)?;
+    eprintln!("Port {preferred} is in use, starting server on {port}");
+    Ok(port)
+}
+
+/// Returns `preferred` if its block is free, otherwise an OS-assigned port
+/// whose block is free. Nothing already listening is touched.
+pub fn pick_port(preferred: u16) -> Result<u16, String> {
+    if block_is_free(preferred) {
+        return Ok(preferred);
+    }
+    for _ in 0..MAX_ATTEMPTS {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|l| l.local_addr())
+            .map_err(|e| format!("Failed to allocate a loopback port: {e}"))?
+            .port();
+        if block_is_free(port) {
+            return Ok(port);
+        }
+    }
+    Err(format!("No free block of {PORT_BLOCK} loopback ports found"))
+}
+
+pub fn is_free(port: u16) -> bool {
+    TcpListener::bind((Ipv4Addr::LOCALHOST, port)).is_ok()
+}
+
+/// Whether `port` and the ports after it that the server also uses are free.
+pub fn block_is_free(port: u16) -> bool {
+    port.checked_add(PORT_BLOCK - 1).is_some() && (0..PORT_BLOCK).all(|i| is_free(port + i))
+}
+
+fn wait_until_free(port: u16) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < RELEASE_TIMEOUT {
+        if block_is_free(port) {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    false
+}
+
+fn confirm_stop(app: &AppHandle, owner: &PortOwner, port: u16) -> bool {
+    app.dialog()
+        .message(format!(
+            "Port {port} is used by {}:\n\n{}\n\nStop it so Stallion can use its usual port, \
+             or start Stallion on a different port?",
+            owner.name(),
+            owner.command
+        ))
+        .title("Port in use")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Stop Process".into(),
+            "Use Another Port".into(),
+        ))
+        .blocking_show()
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) -> bool {
+    // SAFETY: plain syscall on a pid that was just identified as the owner.
+    unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) == 0 }
+}
+
+#[cfg(not(unix))]
+fn terminate(_pid: u32) -> bool {
+    false
+}
+
+/// Finds the listening process via `/proc/net/tcp{,6}` socket inodes and the
+/// `/proc/<pid>/fd` links that point at them.
+#[cfg(target_os = "linux")]
+pub fn find_owner(port: u16) -> Option<PortOwner> {
+    let inodes: Vec<String> = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|table| listening_inodes(&table, port))
+        .collect();
+    if inodes.is_empty() {
+        return None;
+    }
+    let targets: Vec<String> = inodes.iter().map(|i| format!("socket:[{i}]")).collect();
+
+    for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let owns = fds.flatten().any(|fd| {
+            std::fs::read_link(fd.path())
+                .is_ok_and(|link| targets.iter().any(|t| link.as_os_str() == t.as_str()))
+        });
+        if owns {
+            return Some(read_owner(pid));
+        }
+    }
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn listening_inodes(table: &str, port: u16) -> Vec<String> {
+    const LISTEN: &str = "0A";
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let local_port = fields.get(1)?.rsplit(':').next()?;
+            if u16::from_str_radix(local_port, 16).ok()? != port || *fields.get(3)? != LISTEN {
+                return None;
+            }
+            fields.get(9).map(|s| s.to_string())
+        })
+        .filter(|inode| inode != "0")
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn read_owner(pid: u32) -> PortOwner {
+    let proc_dir = PathBuf::from(format!("/proc/{pid}"));
+    let command = std::fs::read(proc_dir.join("cmdline"))
+        .map(|raw| {
+            raw.split(|b| *b == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .unwrap_or_default();
+    let data_dir = std::fs::read(proc_dir.join("environ")).ok().and_then(|raw| {
+        raw.split(|b| *b == 0)
+            .find_map(|var| var.strip_prefix(b"STALLION_AI_DIR="))
+            .map(|dir| PathBuf::from(String::from_utf8_lossy(dir).into_owned()))
+    });
+    PortOwner { pid, command, data_dir }
+}
+
+/// Without `/proc`, fall back to `lsof` and `ps`. The owner's environment
+/// isn't readable here, so only the command line identifies it.
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn find_owner(port: u16) -> Option<PortOwner> {
+    use std::process::Command;
+    let output = Command::new("lsof")
+        .args(["-nP", &format!("-iTCP:{port}"), "-sTCP:LISTEN", "-t"])
+        .output()
+        .ok()?;
+    let pid: u32 = String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    let command = Command::new("ps")
+        .args(["-o", "command=", "-p", &pid.to_string()])
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_default();
+    Some(PortOwner { pid, command, data_dir: None })
+}
+
+#[cfg(not(unix))]
+pub fn find_owner(_port: u16) -> Option<PortOwner> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn listening_inodes_matches_port_and_listen_state() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:0C46 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41231 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:0C47 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41232 1 0000000000000000 100 0 0 10 0
+   2: 0100007F:0C46 0100007F:D2F0 01 00000000:00000000 00:00000000 00000000  1000        0 41299 1 0000000000000000 20 4 30 10 -1
+   3: 0100007F:0C46 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 0 1 0000000000000000 100 0 0 10 0
+";
+        assert_eq!(listening_inodes(table, 3142), vec!["41231"]);
+        assert_eq!(listening_inodes(table, 3143), vec!["41232"]);
+        assert!(listening_inodes(table, 80).is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn listening_inodes_reads_ipv6_addresses() {
+        let table = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000001000000:0C46 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 51234 1 0000000000000000 100 0 0 10 0
+";
+        assert_eq!(listening_inodes(table, 3142), vec!["51234"]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn listening_inodes_skips_malformed_lines() {
+        let table = "header\n\n   0: garbage\n   1: 0100007F:ZZZZ 00000000:0000 0A 0 0 0 0 0 0 1\n";
+        assert!(listening_inodes(table, 3142).is_empty());
+    }
+
+    #[test]
+    fn stale_server_is_this_bundle_or_same_data_dir() {
+        let owner = |command: &str, data_dir: Option<&str>| PortOwner {
+            pid: 1,
+            command: command.into(),
+            data_dir: data_dir.map(PathBuf::from),
+        };
+        let script = Path::new("/Applications/Stallion.app/Contents/Resources/dist-server/index.js");
+        let data_dir = Path::new("/home/me/.stallion-ai");
+
+        assert!(owner(&format!("node {}", script.display()), None).is_stale_server(script, data_dir));
+        assert!(owner("node /other/dist-server/index.js", Some("/home/me/.stallion-ai"))
+            .is_stale_server(script, data_dir));
+        assert!(!owner("node /other/dist-server/index.js", Some("/tmp/else")).is_stale_server(script, data_dir));
+        assert!(!owner("python3 -m http.server 3142", Some("/home/me/.stallion-ai"))
+            .is_stale_server(script, data_dir));
+    }
+
+    #[test]
+    fn owner_name_uses_program_file_name() {
+        let owner = PortOwner { pid: 42, command: "/usr/bin/python3 -m http.server".into(), data_dir: None };
+        assert_eq!(owner.name(), "python3 (pid 42)");
+    }
+}
@@ -0,0 +1,64 @@
+//! Child processes that lead their own process group.
+//!
+//! The server, login shells and auth commands all start helpers of their own
+//! (MCP servers, PTYs, credential daemons, whatever a profile runs). Started
+//! in a fresh group, everything they leave behind can be killed together
+//! with them; elsewhere only the child itself is killed.
+
+use std::process::{Child, Command};
+
+/// Makes `cmd` start as the leader of a new process group, whose id is then
+/// the child's pid.
+pub fn isolate(cmd: &mut Command) {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(cmd, 0);
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
+
+/// SIGKILLs every process left in the group led by `pgid`.
+#[cfg(unix)]
+pub fn kill(pgid: u32) {
+    // SAFETY: plain syscall; ESRCH when the group is already empty is fine.
+    unsafe {
+        libc::killpg(pgid as libc::pid_t, libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn kill(_pgid: u32) {}
+
+/// Kills `child` with its whole group and reaps it.
+pub fn kill_and_reap(child: &mut Child) {
+    #[cfg(not(unix))]
+    let _ = child.kill();
+    kill(child.id());
+    let _ = child.wait();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+
+    #[test]
+    fn kills_what_the_child_started() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 30 & echo $!; wait"]).stdout(Stdio::piped());
+        isolate(&mut cmd);
+        let mut child = cmd.spawn().unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+        let grandchild: libc::pid_t = line.trim().parse().unwrap();
+
+        kill_and_reap(&mut child);
+        // The orphan is reparented and reaped by init, so poll until it's gone.
+        let gone = (0..100).any(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            // SAFETY: signal 0 only checks that the pid exists.
+            unsafe { libc::kill(grandchild, 0) != 0 }
+        });
+        assert!(gone, "sleep {grandchild} survived its group being killed");
+    }
+}
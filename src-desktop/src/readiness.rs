@@ -0,0 +1,41 @@
+//! Server readiness probing.
+//!
+//! A successful TCP connect only means Node has opened its socket; agents,
+//! MCP servers and plugins may still be loading. The server is considered
+//! ready once `/api/system/status` answers `200`, and optionally once the
+//! `STALLION AI STARTED` banner has been printed on stdout.
+
+use crate::http::{self, Response};
+use serde::Serialize;
+use std::time::Duration;
+
+pub const STATUS_PATH: &str = "/api/system/status";
+pub const STARTUP_MARKER: &str = "STALLION AI STARTED";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ReadyPhase {
+    /// Nothing is accepting connections on the port yet.
+    Connecting,
+    /// The socket is open but the status endpoint isn't answering `200`.
+    Initializing,
+    /// The status endpoint is up; waiting for the startup banner.
+    WaitingForStartup,
+}
+
+pub enum Probe {
+    Refused,
+    NotReady,
+    Ready,
+}
+
+/// Issues a single `GET /api/system/status` against the loopback port.
+pub fn probe_status(port: u16) -> Probe {
+    match http::get(port, STATUS_PATH, REQUEST_TIMEOUT) {
+        Response::Refused => Probe::Refused,
+        Response::Ok { status: 200, .. } => Probe::Ready,
+        _ => Probe::NotReady,
+    }
+}
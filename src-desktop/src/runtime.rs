@@ -0,0 +1,94 @@
+//! Runtime configuration handed to the frontend.
+//!
+//! Every app window is built with an initialization script that defines a
+//! frozen `window.__STALLION_RUNTIME__` before any page script runs, so the
+//! frontend never races the shell for its API base and windows opened later
+//! get the same values. `window.__API_BASE__` is still set from it for code
+//! that predates the runtime object.
+//!
+//! The auth token is generated once per app session and also given to the
+//! server as `STALLION_AUTH_TOKEN`. The server itself doesn't check it; it is
+//! there for server code that wants to recognise the app's own webviews.
+//! External pages (research windows) are never given the script.
+
+use serde::Serialize;
+use std::path::Path;
+use tauri::{AppHandle, WebviewUrl, WebviewWindowBuilder, Wry};
+
+pub const TOKEN_VAR: &str = "STALLION_AUTH_TOKEN";
+pub const FEATURES_VAR: &str = "STALLION_FEATURES";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeConfig {
+    pub api_base: String,
+    pub port: u16,
+    pub shell_version: String,
+    pub platform: String,
+    pub arch: String,
+    pub data_dir: String,
+    pub features: Vec<String>,
+    pub auth_token: String,
+}
+
+impl RuntimeConfig {
+    /// `features` is the server's `STALLION_FEATURES` value, if set.
+    pub fn new(
+        app: &AppHandle,
+        port: u16,
+        data_dir: &Path,
+        features: Option<&str>,
+        auth_token: &str,
+    ) -> Self {
+        Self {
+            api_base: format!("http://localhost:{port}"),
+            port,
+            shell_version: app.package_info().version.to_string(),
+            platform: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            data_dir: data_dir.to_string_lossy().into_owned(),
+            features: features
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|f| !f.is_empty())
+                .map(String::from)
+                .collect(),
+            auth_token: auth_token.to_string(),
+        }
+    }
+
+    /// The script run in each top-level document before page scripts.
+    pub fn script(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_else(|_| "{}".into());
+        format!(
+            "Object.defineProperty(window, '__STALLION_RUNTIME__', {{ \
+             value: Object.freeze({json}), writable: false, configurable: false }});\n\
+             window.__API_BASE__ = window.__STALLION_RUNTIME__.apiBase;"
+        )
+    }
+
+    /// A builder for a window showing the app's own frontend, with the
+    /// runtime object injected.
+    pub fn app_window<'a>(
+        &self,
+        app: &'a AppHandle,
+        label: &str,
+        url: &str,
+    ) -> WebviewWindowBuilder<'a, Wry, AppHandle> {
+        WebviewWindowBuilder::new(app, label, WebviewUrl::App(url.into()))
+            .initialization_script(self.script())
+    }
+}
+
+/// A random per-session token, hex encoded.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    if let Err(e) = getrandom::fill(&mut bytes) {
+        // Without OS randomness there is nothing safe to fall back to; an
+        // empty token tells the server not to expect one.
+        eprintln!("Failed to generate auth token: {e}");
+        return String::new();
+    }
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
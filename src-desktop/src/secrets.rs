@@ -0,0 +1,381 @@
+//! Per-plugin secret storage.
+//!
+//! On Linux secrets are kept in the login keyring through the Secret
+//! Service. Without a keyring daemon, and on other platforms for now, they
+//! go to `secrets.enc` in the data directory instead, encrypted with
+//! AES-256-GCM under a key in `secrets.key` that only the user can read.
+//! Either way they stay out of plaintext config and diagnostics bundles;
+//! the file is no defence against other programs running as the same user. Reads, deletes and lists look in both places, so secrets
+//! written while the keyring was unavailable are not lost, and a later write
+//! moves them into the keyring.
+//!
+//! Every secret belongs to a plugin, which must be installed and granted
+//! the `secrets.store` permission. Keys are namespaced by plugin (keyring
+//! attributes, or a map per plugin in the file), so no key of one plugin can
+//! name another plugin's secret and listing only shows the caller's own.
+
+#[cfg(target_os = "linux")]
+use crate::error::{serialize_command_error, CommandError};
+use crate::keyring::Keyring;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+pub const PERMISSION: &str = "secrets.store";
+const GRANTS_FILE: &str = "plugin-grants.json";
+const PLUGIN_DIR: &str = "plugins";
+const STORE_FILE: &str = "secrets.enc";
+const KEY_FILE: &str = "secrets.key";
+/// Starts the store file and authenticates it along with the contents.
+const MAGIC: &[u8] = b"stallion-secrets:1\n";
+const APPLICATION: &str = "stallion-ai";
+const SCHEMA: &str = "ai.stallion.Secret";
+const MAX_NAME_LEN: usize = 128;
+const MAX_VALUE_BYTES: usize = 64 * 1024;
+
+/// Secrets by plugin, then key, as stored in the encrypted file.
+type Entries = BTreeMap<String, BTreeMap<String, String>>;
+
+#[derive(Debug)]
+pub enum SecretError {
+    InvalidName(String),
+    UnknownPlugin { plugin: String },
+    /// The plugin hasn't been granted `secrets.store`.
+    NotPermitted { plugin: String },
+    TooLarge,
+    Keyring(String),
+    Store(String),
+}
+
+impl CommandError for SecretError {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::InvalidName(_) => "invalidName",
+            Self::UnknownPlugin { .. } => "unknownPlugin",
+            Self::NotPermitted { .. } => "notPermitted",
+            Self::TooLarge => "tooLarge",
+            Self::Keyring(_) => "keyring",
+            Self::Store(_) => "store",
+        }
+    }
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidName(message) => write!(f, "{message}"),
+            Self::UnknownPlugin { plugin } => write!(f, "No plugin named {plugin:?} is installed"),
+            Self::NotPermitted { plugin } => {
+                write!(f, "Plugin {plugin:?} does not have the {PERMISSION} permission")
+            }
+            Self::TooLarge => write!(f, "Secrets are limited to {} KiB", MAX_VALUE_BYTES / 1024),
+            Self::Keyring(message) => write!(f, "Keyring error: {message}"),
+            Self::Store(message) => write!(f, "Secret store error: {message}"),
+        }
+    }
+}
+
+serialize_command_error!(SecretError);
+
+pub struct Secrets {
+    data_dir: PathBuf,
+    /// Held across every read-modify-write of the encrypted file.
+    file: Mutex<()>,
+}
+
+impl Secrets {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self {
+            data_dir,
+            file: Mutex::new(()),
+        }
+    }
+
+    /// Checks that `plugin` may use secrets at all.
+    fn authorize(&self, plugin: &str) -> Result<(), SecretError> {
+        validate("plugin", plugin, |c| c.is_ascii_alphanumeric() || "._-".contains(c))?;
+        if plugin.starts_with('.') {
+            return Err(SecretError::InvalidName(format!("Invalid plugin name {plugin:?}")));
+        }
+        if !self.data_dir.join(PLUGIN_DIR).join(plugin).is_dir() {
+            return Err(SecretError::UnknownPlugin { plugin: plugin.into() });
+        }
+        // Grants are written by the server when the user consents.
+        let grants: HashMap<String, Vec<String>> = std::fs::read_to_string(self.data_dir.join(GRANTS_FILE))
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+        if grants.get(plugin).is_some_and(|g| g.iter().any(|p| p == PERMISSION)) {
+            Ok(())
+        } else {
+            Err(SecretError::NotPermitted { plugin: plugin.into() })
+        }
+    }
+
+    fn set(&self, plugin: &str, key: &str, value: &str) -> Result<(), SecretError> {
+        if value.len() > MAX_VALUE_BYTES {
+            return Err(SecretError::TooLarge);
+        }
+        if let Some(keyring) = keyring() {
+            let label = format!("Stallion AI: {plugin} {key}");
+            keyring
+                .set(&label, &attributes(plugin, Some(key)), value)
+                .map_err(SecretError::Keyring)?;
+            // Don't leave an older copy behind in the file.
+            return self.update_file(|entries| remove(entries, plugin, key)).map(|_| ());
+        }
+        self.update_file(|entries| {
+            entries
+                .entry(plugin.to_string())
+                .or_default()
+                .insert(key.to_string(), value.to_string());
+            true
+        })
+        .map(|_| ())
+    }
+
+    fn get(&self, plugin: &str, key: &str) -> Result<Option<String>, SecretError> {
+        if let Some(keyring) = keyring() {
+            if let Some(value) = keyring.get(&attributes(plugin, Some(key))).map_err(SecretError::Keyring)? {
+                return Ok(Some(value));
+            }
+        }
+        let _lock = self.file.lock().unwrap();
+        Ok(self.read_file()?.get(plugin).and_then(|keys| keys.get(key)).cloned())
+    }
+
+    fn delete(&self, plugin: &str, key: &str) -> Result<bool, SecretError> {
+        let in_keyring = match keyring() {
+            Some(keyring) => keyring.delete(&attributes(plugin, Some(key))).map_err(SecretError::Keyring)? > 0,
+            None => false,
+        };
+        let in_file = self.update_file(|entries| remove(entries, plugin, key))?;
+        Ok(in_keyring || in_file)
+    }
+
+    fn list(&self, plugin: &str) -> Result<Vec<String>, SecretError> {
+        let mut keys = BTreeSet::new();
+        if let Some(keyring) = keyring() {
+            let items = keyring.list(&attributes(plugin, None)).map_err(SecretError::Keyring)?;
+            keys.extend(items.into_iter().filter_map(|mut a| a.remove("key")));
+        }
+        let _lock = self.file.lock().unwrap();
+        if let Some(own) = self.read_file()?.remove(plugin) {
+            keys.extend(own.into_keys());
+        }
+        Ok(keys.into_iter().collect())
+    }
+
+    /// Applies `change` to the file's entries and saves them if it reports
+    /// a change, which it also returns.
+    fn update_file(&self, change: impl FnOnce(&mut Entries) -> bool) -> Result<bool, SecretError> {
+        let _lock = self.file.lock().unwrap();
+        let mut entries = self.read_file()?;
+        let changed = change(&mut entries);
+        if changed {
+            self.write_file(&entries)?;
+        }
+        Ok(changed)
+    }
+
+    fn read_file(&self) -> Result<Entries, SecretError> {
+        let path = self.data_dir.join(STORE_FILE);
+        let mut sealed = match std::fs::read(&path) {
+            Ok(sealed) => sealed,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Entries::new()),
+            Err(e) => return Err(SecretError::Store(format!("Failed to read {}: {e}", path.display()))),
+        };
+        let Some(key) = self.key(false)? else {
+            return Err(SecretError::Store(format!(
+                "{} exists but {KEY_FILE} is missing, so it can't be decrypted",
+                path.display()
+            )));
+        };
+        let corrupt = || SecretError::Store(format!("{} is corrupt", path.display()));
+        if !sealed.starts_with(MAGIC) || sealed.len() < MAGIC.len() + NONCE_LEN {
+            return Err(corrupt());
+        }
+        let mut body = sealed.split_off(MAGIC.len() + NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&sealed[MAGIC.len()..]).map_err(|_| corrupt())?;
+        let plain = key
+            .open_in_place(nonce, Aad::from(MAGIC), &mut body)
+            .map_err(|_| corrupt())?;
+        serde_json::from_slice(plain).map_err(|_| corrupt())
+    }
+
+    fn write_file(&self, entries: &Entries) -> Result<(), SecretError> {
+        let key = self.key(true)?.expect("created on demand");
+        let mut nonce = [0u8; NONCE_LEN];
+        getrandom::fill(&mut nonce).map_err(|e| SecretError::Store(format!("No randomness for a nonce: {e}")))?;
+        let mut body = serde_json::to_vec(entries).map_err(|e| SecretError::Store(e.to_string()))?;
+        key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(MAGIC), &mut body)
+            .map_err(|_| SecretError::Store("Encryption failed".into()))?;
+        let sealed = [MAGIC, &nonce, &body].concat();
+
+        // Written beside the store and renamed over it, so a crash never
+        // leaves half a file.
+        let path = self.data_dir.join(STORE_FILE);
+        let temp = path.with_extension("enc.tmp");
+        write_private(&temp, &sealed)
+            .and_then(|()| std::fs::rename(&temp, &path))
+            .map_err(|e| SecretError::Store(format!("Failed to write {}: {e}", path.display())))
+    }
+
+    /// The file encryption key, created first if `create` is set.
+    fn key(&self, create: bool) -> Result<Option<LessSafeKey>, SecretError> {
+        let path = self.data_dir.join(KEY_FILE);
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && create => {
+                let mut bytes = vec![0u8; AES_256_GCM.key_len()];
+                getrandom::fill(&mut bytes)
+                    .map_err(|e| SecretError::Store(format!("No randomness for a key: {e}")))?;
+                write_private(&path, &bytes)
+                    .map_err(|e| SecretError::Store(format!("Failed to write {}: {e}", path.display())))?;
+                bytes
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(SecretError::Store(format!("Failed to read {}: {e}", path.display()))),
+        };
+        UnboundKey::new(&AES_256_GCM, &bytes)
+            .map(|key| Some(LessSafeKey::new(key)))
+            .map_err(|_| SecretError::Store(format!("{} is not a valid key", path.display())))
+    }
+}
+
+fn validate(what: &str, name: &str, allowed: impl Fn(char) -> bool) -> Result<(), SecretError> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN || !name.chars().all(allowed) {
+        return Err(SecretError::InvalidName(format!("Invalid {what} name {name:?}")));
+    }
+    Ok(())
+}
+
+fn validate_key(key: &str) -> Result<(), SecretError> {
+    validate("secret", key, |c| c.is_ascii_alphanumeric() || "._-:/".contains(c))
+}
+
+fn remove(entries: &mut Entries, plugin: &str, key: &str) -> bool {
+    let Some(keys) = entries.get_mut(plugin) else {
+        return false;
+    };
+    let removed = keys.remove(key).is_some();
+    if keys.is_empty() {
+        entries.remove(plugin);
+    }
+    removed
+}
+
+/// Keyring attributes of one secret, or of all of a plugin's.
+fn attributes<'a>(plugin: &'a str, key: Option<&'a str>) -> HashMap<&'a str, &'a str> {
+    let mut attributes = HashMap::from([("xdg:schema", SCHEMA), ("application", APPLICATION), ("plugin", plugin)]);
+    if let Some(key) = key {
+        attributes.insert("key", key);
+    }
+    attributes
+}
+
+#[cfg(target_os = "linux")]
+fn keyring() -> Option<Keyring> {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    Keyring::connect()
+        .inspect_err(|e| WARNED.call_once(|| eprintln!("{e}; storing secrets in the encrypted file")))
+        .ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn keyring() -> Option<Keyring> {
+    None
+}
+
+/// Stands in for the Secret Service client where there isn't one yet, so
+/// only the file is used.
+#[cfg(not(target_os = "linux"))]
+enum Keyring {}
+
+#[cfg(not(target_os = "linux"))]
+impl Keyring {
+    fn get(&self, _: &HashMap<&str, &str>) -> Result<Option<String>, String> {
+        match *self {}
+    }
+
+    fn set(&self, _: &str, _: &HashMap<&str, &str>, _: &str) -> Result<(), String> {
+        match *self {}
+    }
+
+    fn delete(&self, _: &HashMap<&str, &str>) -> Result<usize, String> {
+        match *self {}
+    }
+
+    fn list(&self, _: &HashMap<&str, &str>) -> Result<Vec<HashMap<String, String>>, String> {
+        match *self {}
+    }
+}
+
+/// Writes a file only the user can read.
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Runs `operation` for `plugin` off the main thread, since the keyring may
+/// have to wait for the user to unlock it.
+async fn with_plugin<T: Send + 'static>(
+    secrets: &Arc<Secrets>,
+    plugin: String,
+    operation: impl FnOnce(&Secrets, &str) -> Result<T, SecretError> + Send + 'static,
+) -> Result<T, SecretError> {
+    let secrets = secrets.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        secrets.authorize(&plugin)?;
+        operation(&secrets, &plugin)
+    })
+    .await
+    .map_err(|e| SecretError::Store(e.to_string()))?
+}
+
+#[tauri::command]
+pub async fn secret_set(
+    plugin: String,
+    key: String,
+    value: String,
+    secrets: State<'_, Arc<Secrets>>,
+) -> Result<(), SecretError> {
+    validate_key(&key)?;
+    with_plugin(&secrets, plugin, move |s, plugin| s.set(plugin, &key, &value)).await
+}
+
+#[tauri::command]
+pub async fn secret_get(
+    plugin: String,
+    key: String,
+    secrets: State<'_, Arc<Secrets>>,
+) -> Result<Option<String>, SecretError> {
+    validate_key(&key)?;
+    with_plugin(&secrets, plugin, move |s, plugin| s.get(plugin, &key)).await
+}
+
+/// Returns whether there was a secret to delete.
+#[tauri::command]
+pub async fn secret_delete(
+    plugin: String,
+    key: String,
+    secrets: State<'_, Arc<Secrets>>,
+) -> Result<bool, SecretError> {
+    validate_key(&key)?;
+    with_plugin(&secrets, plugin, move |s, plugin| s.delete(plugin, &key)).await
+}
+
+/// The keys of the plugin's secrets, never their values.
+#[tauri::command]
+pub async fn secret_list(plugin: String, secrets: State<'_, Arc<Secrets>>) -> Result<Vec<String>, SecretError> {
+    with_plugin(&secrets, plugin, |s, plugin| s.list(plugin)).await
+}
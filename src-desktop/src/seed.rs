@@ -0,0 +1,291 @@
+//! Versioned seeding of the bundled default configs into the data dir.
+//!
+//! `seed/manifest.json` carries a version that is bumped whenever the
+//! defaults change. When the bundle's version is newer than the one recorded
+//! in `<data dir>/.seed.json`, every file under `seed/config` is applied:
+//!
+//! - missing files are copied as-is;
+//! - existing JSON files gain any default keys they lack, recursively, and
+//!   values the user already has are never touched;
+//! - a key the user deleted after an earlier seed provided it stays deleted,
+//!   using the defaults snapshot recorded with the previous version.
+//!
+//! The new version is only recorded once every file has been applied, so a
+//! config that couldn't be merged (say, it isn't valid JSON right now) is
+//! tried again on the next launch. Every change is returned so it can be
+//! logged.
+
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const MANIFEST_FILE: &str = "manifest.json";
+const STATE_FILE: &str = ".seed.json";
+const SEEDED_DIRS: &[&str] = &["config"];
+
+#[derive(Deserialize)]
+struct Manifest {
+    version: u32,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct SeedState {
+    version: u32,
+    applied_at: String,
+    /// The JSON defaults applied last time, keyed by path relative to the
+    /// data dir.
+    defaults: BTreeMap<String, Value>,
+}
+
+/// Applies the bundled seed if it is newer than the last one applied.
+/// Returns a description of every change made.
+pub fn apply(seed_dir: &Path, data_dir: &Path) -> Result<Vec<String>, String> {
+    let manifest_path = seed_dir.join(MANIFEST_FILE);
+    let manifest: Manifest = read_json(&manifest_path)?;
+    let state_path = data_dir.join(STATE_FILE);
+    let previous: SeedState = if state_path.exists() {
+        read_json(&state_path).unwrap_or_else(|e| {
+            eprintln!("Ignoring {e}");
+            SeedState::default()
+        })
+    } else {
+        SeedState::default()
+    };
+    if previous.version >= manifest.version {
+        return Ok(Vec::new());
+    }
+
+    let mut changes = Vec::new();
+    let mut defaults = BTreeMap::new();
+    let mut skipped = 0;
+    for dir in SEEDED_DIRS {
+        for source in files_under(&seed_dir.join(dir)) {
+            let rel = source
+                .strip_prefix(seed_dir)
+                .unwrap_or(&source)
+                .to_string_lossy()
+                .replace('\\', "/");
+            let target = data_dir.join(&rel);
+            let seed: Option<Value> = if source.extension().is_some_and(|e| e == "json") {
+                Some(read_json(&source)?)
+            } else {
+                None
+            };
+
+            if !target.exists() {
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                std::fs::copy(&source, &target)
+                    .map_err(|e| format!("Failed to copy {rel}: {e}"))?;
+                changes.push(format!("added {rel}"));
+            } else if let Some(seed) = &seed {
+                match merge_file(&target, seed, previous.defaults.get(&rel)) {
+                    Ok(added) if added.is_empty() => {}
+                    Ok(added) => changes.push(format!("{rel}: added {}", added.join(", "))),
+                    Err(e) => {
+                        changes.push(format!("skipped {rel}: {e}"));
+                        skipped += 1;
+                        // Its old snapshot still describes what the user has.
+                        if let Some(old) = previous.defaults.get(&rel) {
+                            defaults.insert(rel, old.clone());
+                        }
+                        continue;
+                    }
+                }
+            }
+            if let Some(seed) = seed {
+                defaults.insert(rel, seed);
+            }
+        }
+    }
+
+    let version = if skipped == 0 { manifest.version } else { previous.version };
+    let state = SeedState {
+        version,
+        applied_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        defaults,
+    };
+    write_json(&state_path, &serde_json::to_value(&state).map_err(|e| e.to_string())?)?;
+    if skipped == 0 {
+        changes.push(format!(
+            "seed version {} -> {}",
+            previous.version, manifest.version
+        ));
+    } else {
+        changes.push(format!(
+            "seed version {} kept; {skipped} file(s) will be retried next launch",
+            previous.version
+        ));
+    }
+    Ok(changes)
+}
+
+/// Adds the seed's missing keys to the JSON file at `target`, returning the
+/// dotted paths of the keys added.
+fn merge_file(target: &Path, seed: &Value, previous: Option<&Value>) -> Result<Vec<String>, String> {
+    let mut current: Value = read_json(target)?;
+    let mut added = Vec::new();
+    merge(&mut current, seed, previous, "", &mut added);
+    if !added.is_empty() {
+        write_json(target, &current)?;
+    }
+    Ok(added)
+}
+
+fn merge(current: &mut Value, seed: &Value, previous: Option<&Value>, path: &str, added: &mut Vec<String>) {
+    let (Value::Object(current), Value::Object(seed)) = (current, seed) else {
+        // Scalars, arrays and type changes are the user's to keep.
+        return;
+    };
+    for (key, default) in seed {
+        let key_path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{path}.{key}")
+        };
+        let previous = previous.and_then(|p| p.get(key));
+        match current.get_mut(key) {
+            Some(existing) => merge(existing, default, previous, &key_path, added),
+            // Offered before and since removed by the user: leave it out.
+            None if previous.is_some() => {}
+            None => {
+                current.insert(key.clone(), default.clone());
+                added.push(key_path);
+            }
+        }
+    }
+}
+
+fn files_under(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files = Vec::new();
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            files.extend(files_under(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    serde_json::from_str(&raw).map_err(|e| format!("{} is not valid JSON: {e}", path.display()))
+}
+
+/// Writes through a temporary file so a crash can't leave a config
+/// half-written.
+fn write_json(path: &Path, value: &Value) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json + "\n")
+        .and_then(|()| std::fs::rename(&tmp, path))
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn merged(current: Value, seed: Value, previous: Option<Value>) -> (Value, Vec<String>) {
+        let mut current = current;
+        let mut added = Vec::new();
+        merge(&mut current, &seed, previous.as_ref(), "", &mut added);
+        (current, added)
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("stallion-seed-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: &Path, contents: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn adds_missing_keys_recursively() {
+        let (value, added) = merged(
+            json!({ "theme": "dark", "editor": { "tabs": 2 } }),
+            json!({ "theme": "light", "telemetry": false, "editor": { "tabs": 4, "wrap": true } }),
+            None,
+        );
+        assert_eq!(value, json!({ "theme": "dark", "telemetry": false, "editor": { "tabs": 2, "wrap": true } }));
+        assert_eq!(added, ["telemetry", "editor.wrap"]);
+    }
+
+    #[test]
+    fn keeps_user_values_and_types() {
+        let (value, added) = merged(
+            json!({ "models": ["mine"], "limits": 5, "port": "3142" }),
+            json!({ "models": ["a", "b"], "limits": { "max": 10 }, "port": 3142 }),
+            None,
+        );
+        assert_eq!(value, json!({ "models": ["mine"], "limits": 5, "port": "3142" }));
+        assert!(added.is_empty());
+    }
+
+    #[test]
+    fn deleted_keys_stay_deleted() {
+        let previous = json!({ "telemetry": true, "editor": { "wrap": true } });
+        let (value, added) = merged(
+            json!({ "editor": {} }),
+            json!({ "telemetry": true, "editor": { "wrap": true, "minimap": false }, "new": 1 }),
+            Some(previous),
+        );
+        assert_eq!(value, json!({ "editor": { "minimap": false }, "new": 1 }));
+        assert_eq!(added, ["editor.minimap", "new"]);
+    }
+
+    #[test]
+    fn failed_merges_are_retried() {
+        let root = temp_dir("retry");
+        let (seed, data) = (root.join("seed"), root.join("data"));
+        write(&seed.join(MANIFEST_FILE), r#"{ "version": 2 }"#);
+        write(&seed.join("config/app.json"), r#"{ "a": 1, "b": 2 }"#);
+        write(&seed.join("config/other.json"), r#"{ "x": 1 }"#);
+        write(&data.join("config/app.json"), "{ not json");
+        write(&data.join("config/other.json"), "{}");
+
+        let changes = apply(&seed, &data).unwrap();
+        assert!(changes.iter().any(|c| c.starts_with("skipped config/app.json")), "{changes:?}");
+        assert!(changes.contains(&"config/other.json: added x".to_string()));
+        let state: SeedState = read_json(&data.join(STATE_FILE)).unwrap();
+        assert_eq!(state.version, 0);
+
+        // Once the user fixes the file, the next launch merges it.
+        write(&data.join("config/app.json"), r#"{ "a": 5 }"#);
+        let changes = apply(&seed, &data).unwrap();
+        assert_eq!(changes, ["config/app.json: added b", "seed version 0 -> 2"]);
+        let app: Value = read_json(&data.join("config/app.json")).unwrap();
+        assert_eq!(app, json!({ "a": 5, "b": 2 }));
+        assert!(apply(&seed, &data).unwrap().is_empty());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn copies_missing_files_once() {
+        let root = temp_dir("copy");
+        let (seed, data) = (root.join("seed"), root.join("data"));
+        write(&seed.join(MANIFEST_FILE), r#"{ "version": 1 }"#);
+        write(&seed.join("config/agents/default.json"), r#"{ "name": "default" }"#);
+
+        assert_eq!(apply(&seed, &data).unwrap(), ["added config/agents/default.json", "seed version 0 -> 1"]);
+        std::fs::remove_file(data.join("config/agents/default.json")).unwrap();
+        assert!(apply(&seed, &data).unwrap().is_empty(), "same version is not re-applied");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
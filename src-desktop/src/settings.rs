@@ -0,0 +1,242 @@
+//! Desktop shell settings, read from `<data dir>/desktop.json`.
+//!
+//! Every field is optional; anything missing falls back to the defaults below
+//! so an absent or partial file behaves exactly like a fresh install.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const SETTINGS_FILE: &str = "desktop.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DesktopSettings {
+    pub server: ServerSettings,
+    pub logs: LogSettings,
+    pub runtime: RuntimeSettings,
+    pub shell_env: ShellEnvSettings,
+    pub env: EnvSettings,
+    pub auth: AuthSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ServerSettings {
+    /// Consecutive crashes tolerated before the supervisor gives up.
+    pub max_restarts: u32,
+    /// Delay before the first restart; doubled on every consecutive crash.
+    pub restart_backoff_ms: u64,
+    /// Upper bound for the restart delay.
+    pub max_backoff_ms: u64,
+    /// How long to wait for the server to report ready after a spawn.
+    pub ready_timeout_ms: u64,
+    /// Also wait for the `STALLION AI STARTED` banner on stdout.
+    pub wait_for_startup_line: bool,
+    /// Time the server gets to flush and stop its children after SIGTERM
+    /// before its whole process group is killed.
+    pub drain_timeout_ms: u64,
+    /// Most recent stderr lines kept for crash reports.
+    pub crash_report_lines: usize,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            restart_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            ready_timeout_ms: 60_000,
+            wait_for_startup_line: false,
+            drain_timeout_ms: 10_000,
+            crash_report_lines: 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogSettings {
+    /// Rotate `server.log` once it would grow past this size.
+    pub max_file_bytes: u64,
+    /// Rotate `server.log` once it is older than this.
+    pub max_age_hours: u64,
+    /// Rotated files to keep.
+    pub max_files: usize,
+    /// Rotated files older than this are deleted regardless of count.
+    pub retention_days: u64,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 10 * 1024 * 1024,
+            max_age_hours: 24,
+            max_files: 10,
+            retention_days: 14,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RuntimeSettings {
+    /// Explicit Node binary; only `STALLION_NODE` takes precedence.
+    pub node_path: Option<String>,
+    /// Where the Node shipped inside the app bundle ranks against the
+    /// runtimes discovered on the machine.
+    pub bundled_node: BundledNode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BundledNode {
+    /// Use the bundled Node unless one is configured explicitly.
+    #[default]
+    First,
+    /// Prefer version managers, the login PATH and system installs.
+    Last,
+    /// Never use the bundled Node.
+    Never,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ShellEnvSettings {
+    /// How long the login shell may take to report its environment.
+    pub timeout_ms: u64,
+    /// A captured environment is reused for this long unless a shell
+    /// profile changes first.
+    pub cache_ttl_hours: u64,
+}
+
+impl Default for ShellEnvSettings {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 10_000,
+            cache_ttl_hours: 24,
+        }
+    }
+}
+
+/// Which login shell variables reach the server. Patterns match whole
+/// names, with `*` as a wildcard; `deny` wins over `allow`. Variables from
+/// `desktop.env` are explicit and bypass both lists.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EnvSettings {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+impl Default for EnvSettings {
+    fn default() -> Self {
+        Self {
+            allow: vec!["*".into()],
+            deny: Vec::new(),
+        }
+    }
+}
+
+/// The external auth commands `authenticate_external` can run, by id.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuthSettings {
+    pub providers: Vec<AuthProvider>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuthProvider {
+    /// What the UI passes to `authenticate_external`, e.g. `aws-sso`.
+    pub id: String,
+    /// Shown to the user; defaults to the id.
+    pub name: Option<String>,
+    /// Program and arguments, run directly rather than through a shell.
+    pub command: Vec<String>,
+    /// The command is killed (with anything it started) after this long.
+    pub timeout_ms: u64,
+    /// Write the PIN and a newline to the command's stdin. Providers that
+    /// don't take a PIN should turn this off.
+    pub pin_stdin: bool,
+    /// Keep stdin open after the PIN so the UI can answer follow-up
+    /// prompts. Commands that read until end of input need this off.
+    pub interactive: bool,
+    /// Open the first URL the command prints in the system browser.
+    pub open_browser: bool,
+    /// How to tell that the command succeeded.
+    pub success: SuccessCheck,
+    /// Exit codes that mean the PIN was rejected.
+    pub bad_pin_exit_codes: Vec<i32>,
+    /// A regex that, matching stdout or stderr, means the PIN was rejected.
+    pub bad_pin_pattern: Option<String>,
+}
+
+impl Default for AuthProvider {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            name: None,
+            command: Vec::new(),
+            timeout_ms: 120_000,
+            pin_stdin: true,
+            interactive: false,
+            open_browser: false,
+            success: SuccessCheck::default(),
+            bad_pin_exit_codes: Vec::new(),
+            bad_pin_pattern: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum SuccessCheck {
+    /// The command exits with one of `codes`.
+    ExitCode { codes: Vec<i32> },
+    /// Stdout (or its last line) is JSON whose value at `pointer` (RFC 6901,
+    /// e.g. `/status`) equals `equals`.
+    Json {
+        pointer: String,
+        equals: serde_json::Value,
+    },
+    /// Stdout matches the regex `pattern`.
+    Regex { pattern: String },
+}
+
+impl Default for SuccessCheck {
+    fn default() -> Self {
+        Self::ExitCode { codes: vec![0] }
+    }
+}
+
+impl DesktopSettings {
+    pub fn load(data_dir: &Path) -> Self {
+        let path = data_dir.join(SETTINGS_FILE);
+        let Ok(raw) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&raw).unwrap_or_else(|e| {
+            eprintln!("Ignoring invalid {}: {e}", path.display());
+            Self::default()
+        })
+    }
+
+    /// Replaces one top-level section of `desktop.json`, leaving the rest of
+    /// the file (including keys this version doesn't know) untouched.
+    pub fn write_section<T: Serialize>(data_dir: &Path, key: &str, section: &T) -> Result<(), String> {
+        let path = data_dir.join(SETTINGS_FILE);
+        let mut root = match std::fs::read_to_string(&path) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .map_err(|e| format!("{} is not valid JSON: {e}", path.display()))?,
+            Err(_) => serde_json::Value::Object(Default::default()),
+        };
+        let Some(object) = root.as_object_mut() else {
+            return Err(format!("{} is not a JSON object", path.display()));
+        };
+        let value = serde_json::to_value(section).map_err(|e| e.to_string())?;
+        object.insert(key.to_string(), value);
+        let json = serde_json::to_string_pretty(&root).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json + "\n")
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+    }
+}
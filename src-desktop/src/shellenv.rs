@@ -0,0 +1,439 @@
+//! Login shell environment capture.
+//!
+//! Apps launched from the Dock or a desktop menu get a minimal environment,
+//! so the server would miss `PATH` additions, `AWS_PROFILE`, proxy settings
+//! and SDK homes set up in the user's shell profile. The shell is run once
+//! as an interactive login shell and asked for `env -0`, which survives
+//! values containing newlines and works the same from bash, zsh, fish and
+//! nushell. Anything the profile prints itself is discarded by fencing the
+//! output with a random marker.
+//!
+//! Capturing is bounded by a timeout (a slow or hung `.zshrc` must not block
+//! startup) and the result is cached under `<data dir>/cache/`. The cache is
+//! reused until it expires, the login shell changes, or one of the profile
+//! files is modified; `refresh_shell_env` recaptures on demand.
+
+use crate::procgroup;
+use crate::settings::ShellEnvSettings;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::State;
+
+const CACHE_FILE: &str = "cache/shell-env.json";
+/// Per-shell bookkeeping that shouldn't leak into the server.
+const IGNORED_VARS: &[&str] = &["_", "SHLVL", "PWD", "OLDPWD"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EnvSource {
+    /// Captured from the login shell during this launch.
+    Captured,
+    /// Reused from a cache that is still valid.
+    Cached,
+    /// Capturing failed; an outdated cache was used instead.
+    Stale,
+    /// Capturing failed and nothing was cached; the app's own environment.
+    Inherited,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShellEnv {
+    pub shell: PathBuf,
+    pub source: EnvSource,
+    /// Seconds since the Unix epoch.
+    pub captured_at: u64,
+    pub vars: BTreeMap<String, String>,
+}
+
+impl ShellEnv {
+    pub fn summary(&self) -> EnvSummary {
+        EnvSummary {
+            shell: self.shell.clone(),
+            source: self.source,
+            captured_at: self.captured_at,
+            variables: self.vars.len(),
+        }
+    }
+}
+
+/// What the UI gets to see; the values themselves may hold secrets.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvSummary {
+    pub shell: PathBuf,
+    pub source: EnvSource,
+    pub captured_at: u64,
+    pub variables: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheEntry {
+    env: ShellEnv,
+    /// Modification times (ms) of the profile files that existed at capture.
+    profiles: BTreeMap<PathBuf, u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShellKind {
+    Posix,
+    Fish,
+    Nu,
+    /// csh, tcsh, xonsh, elvish...: no portable login/interactive flags.
+    Other,
+}
+
+impl ShellKind {
+    fn of(shell: &Path) -> Self {
+        match shell.file_name().and_then(|n| n.to_str()).unwrap_or_default() {
+            "bash" | "zsh" | "sh" | "dash" | "ksh" | "mksh" | "yash" => ShellKind::Posix,
+            "fish" => ShellKind::Fish,
+            "nu" | "nushell" => ShellKind::Nu,
+            _ => ShellKind::Other,
+        }
+    }
+
+    fn args(self, marker: &str) -> Vec<String> {
+        let posix = format!("printf '%s' '{marker}'; env -0; printf '%s' '{marker}'");
+        match self {
+            ShellKind::Posix => vec!["-i".into(), "-l".into(), "-c".into(), posix],
+            ShellKind::Fish => vec!["--login".into(), "--interactive".into(), "-c".into(), posix],
+            // `env` is not a nushell command; `^env` runs the external one
+            // with nushell's environment converted to strings.
+            ShellKind::Nu => vec![
+                "--login".into(),
+                "--interactive".into(),
+                "--commands".into(),
+                format!("print --no-newline '{marker}'; ^env -0; print --no-newline '{marker}'"),
+            ],
+            ShellKind::Other => vec!["-c".into(), posix],
+        }
+    }
+}
+
+pub struct ShellEnvResolver {
+    home: PathBuf,
+    shell: PathBuf,
+    cache_path: PathBuf,
+    settings: ShellEnvSettings,
+}
+
+impl ShellEnvResolver {
+    pub fn new(home: &Path, data_dir: &Path, settings: ShellEnvSettings) -> Self {
+        let shell = std::env::var_os("SHELL")
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(if cfg!(target_os = "macos") { "/bin/zsh" } else { "/bin/sh" }));
+        Self {
+            home: home.to_path_buf(),
+            shell,
+            cache_path: data_dir.join(CACHE_FILE),
+            settings,
+        }
+    }
+
+    /// The login environment, from a valid cache when possible.
+    pub fn resolve(&self) -> ShellEnv {
+        let cached = self.read_cache();
+        if let Some(entry) = &cached {
+            if self.is_fresh(entry) {
+                let mut env = entry.env.clone();
+                env.source = EnvSource::Cached;
+                return env;
+            }
+        }
+        match self.capture() {
+            Ok(env) => env,
+            Err(e) => {
+                eprintln!("Failed to capture the login environment from {}: {e}", self.shell.display());
+                match cached.filter(|c| c.env.shell == self.shell) {
+                    Some(entry) => ShellEnv {
+                        source: EnvSource::Stale,
+                        ..entry.env
+                    },
+                    None => ShellEnv {
+                        shell: self.shell.clone(),
+                        source: EnvSource::Inherited,
+                        captured_at: now_secs(),
+                        vars: std::env::vars().collect(),
+                    },
+                }
+            }
+        }
+    }
+
+    /// Runs the login shell now and caches the result.
+    pub fn capture(&self) -> Result<ShellEnv, String> {
+        let mut nonce = [0u8; 16];
+        getrandom::fill(&mut nonce).map_err(|e| format!("no randomness for the output marker: {e}"))?;
+        let marker = format!(
+            "__STALLION_ENV_{}__",
+            nonce.iter().map(|b| format!("{b:02x}")).collect::<String>()
+        );
+        let output = self.run_shell(&marker)?;
+        let vars = parse_env_output(&output, &marker)
+            .ok_or_else(|| "shell output did not contain an `env -0` listing".to_string())?;
+        let env = ShellEnv {
+            shell: self.shell.clone(),
+            source: EnvSource::Captured,
+            captured_at: now_secs(),
+            vars,
+        };
+        self.write_cache(&env);
+        Ok(env)
+    }
+
+    fn run_shell(&self, marker: &str) -> Result<Vec<u8>, String> {
+        let mut cmd = Command::new(&self.shell);
+        cmd.args(ShellKind::of(&self.shell).args(marker))
+            .current_dir(&self.home)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        procgroup::isolate(&mut cmd);
+        let mut child = cmd.spawn().map_err(|e| format!("failed to start: {e}"))?;
+
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stdout.read_to_end(&mut buf);
+            let _ = tx.send(buf);
+        });
+
+        let timeout = Duration::from_millis(self.settings.timeout_ms);
+        let start = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if start.elapsed() < timeout => {
+                    std::thread::sleep(Duration::from_millis(25))
+                }
+                Ok(None) => {
+                    procgroup::kill_and_reap(&mut child);
+                    return Err(format!("timed out after {}ms", self.settings.timeout_ms));
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+        };
+        // A profile may leave a background job holding stdout open; kill
+        // its group, and don't wait on one that escaped it.
+        procgroup::kill_and_reap(&mut child);
+        let output = rx
+            .recv_timeout(timeout.saturating_sub(start.elapsed()).max(Duration::from_secs(1)))
+            .map_err(|_| "shell output was never closed".to_string())?;
+        if !status.success() && output.is_empty() {
+            return Err(format!("exited with {status}"));
+        }
+        Ok(output)
+    }
+
+    fn is_fresh(&self, entry: &CacheEntry) -> bool {
+        let ttl = self.settings.cache_ttl_hours * 3600;
+        entry.env.shell == self.shell
+            && now_secs().saturating_sub(entry.env.captured_at) < ttl
+            && entry.profiles == self.profile_stamps()
+    }
+
+    fn read_cache(&self) -> Option<CacheEntry> {
+        let raw = std::fs::read(&self.cache_path).ok()?;
+        serde_json::from_slice(&raw).ok()
+    }
+
+    fn write_cache(&self, env: &ShellEnv) {
+        let entry = CacheEntry {
+            env: env.clone(),
+            profiles: self.profile_stamps(),
+        };
+        let Ok(json) = serde_json::to_vec(&entry) else {
+            return;
+        };
+        if let Some(dir) = self.cache_path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Err(e) = write_private(&self.cache_path, &json) {
+            eprintln!("Failed to cache the login environment: {e}");
+        }
+    }
+
+    /// Files whose changes can alter the login environment, with their
+    /// modification times. A file appearing or disappearing also counts.
+    fn profile_stamps(&self) -> BTreeMap<PathBuf, u64> {
+        let zdotdir = std::env::var_os("ZDOTDIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| self.home.clone());
+        let mut files: Vec<PathBuf> = [
+            ".profile",
+            ".bash_profile",
+            ".bash_login",
+            ".bashrc",
+            ".config/fish/config.fish",
+            ".config/fish/conf.d",
+            ".config/nushell/env.nu",
+            ".config/nushell/config.nu",
+            "Library/Application Support/nushell/env.nu",
+            "Library/Application Support/nushell/config.nu",
+        ]
+        .iter()
+        .map(|f| self.home.join(f))
+        .collect();
+        files.extend(
+            [".zshenv", ".zprofile", ".zshrc", ".zlogin"]
+                .iter()
+                .map(|f| zdotdir.join(f)),
+        );
+        files.extend(
+            [
+                "/etc/profile",
+                "/etc/profile.d",
+                "/etc/bashrc",
+                "/etc/bash.bashrc",
+                "/etc/zshenv",
+                "/etc/zprofile",
+                "/etc/zshrc",
+                "/etc/zsh/zshenv",
+                "/etc/zsh/zprofile",
+                "/etc/zsh/zshrc",
+                "/etc/paths",
+                "/etc/paths.d",
+                "/etc/environment",
+            ]
+            .iter()
+            .map(PathBuf::from),
+        );
+        files
+            .into_iter()
+            .filter_map(|path| {
+                let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+                let ms = modified.duration_since(UNIX_EPOCH).ok()?.as_millis() as u64;
+                Some((path, ms))
+            })
+            .collect()
+    }
+}
+
+/// Recaptures the login environment, replacing the cache. The running
+/// server keeps its environment until it is restarted.
+#[tauri::command]
+pub async fn refresh_shell_env(
+    resolver: State<'_, Arc<ShellEnvResolver>>,
+) -> Result<EnvSummary, String> {
+    let resolver = resolver.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || resolver.capture())
+        .await
+        .map_err(|e| e.to_string())?
+        .map(|env| env.summary())
+}
+
+/// Extracts `KEY=value` pairs from the NUL-delimited listing between the
+/// first and last marker.
+fn parse_env_output(output: &[u8], marker: &str) -> Option<BTreeMap<String, String>> {
+    let marker = marker.as_bytes();
+    let start = find(output, marker)? + marker.len();
+    let end = start + rfind(&output[start..], marker)?;
+    let vars = output[start..end]
+        .split(|b| *b == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (key, value) = entry.split_once('=')?;
+            (!key.is_empty() && !IGNORED_VARS.contains(&key))
+                .then(|| (key.to_string(), value.to_string()))
+        })
+        .collect::<BTreeMap<_, _>>();
+    (!vars.is_empty()).then_some(vars)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+/// Writes a file readable by the user only; the cache holds whatever
+/// secrets the profile exports.
+pub fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARKER: &str = "__STALLION_ENV_0123456789abcdef__";
+
+    fn framed(listing: &[u8]) -> Vec<u8> {
+        [MARKER.as_bytes(), listing, MARKER.as_bytes()].concat()
+    }
+
+    #[test]
+    fn parses_listing_between_markers() {
+        let vars = parse_env_output(&framed(b"HOME=/home/me\0PATH=/usr/bin:/bin\0EMPTY=\0"), MARKER).unwrap();
+        assert_eq!(vars["HOME"], "/home/me");
+        assert_eq!(vars["PATH"], "/usr/bin:/bin");
+        assert_eq!(vars["EMPTY"], "");
+        assert_eq!(vars.len(), 3);
+    }
+
+    #[test]
+    fn keeps_newlines_and_equals_in_values() {
+        let listing = b"CERT=-----BEGIN-----\nabc=\n-----END-----\0PS1=$ \0URL=https://x?a=b\0";
+        let vars = parse_env_output(&framed(listing), MARKER).unwrap();
+        assert_eq!(vars["CERT"], "-----BEGIN-----\nabc=\n-----END-----");
+        assert_eq!(vars["URL"], "https://x?a=b");
+    }
+
+    #[test]
+    fn ignores_profile_output_around_the_markers() {
+        let output = [
+            b"Welcome back!\nFOO=not-a-var\n\x1b[32mnvm loaded\x1b[0m\n".as_slice(),
+            &framed(b"REAL=1\0"),
+            b"\nlogout\nBAR=after\n",
+        ]
+        .concat();
+        let vars = parse_env_output(&output, MARKER).unwrap();
+        assert_eq!(vars.keys().collect::<Vec<_>>(), ["REAL"]);
+    }
+
+    #[test]
+    fn skips_shell_bookkeeping_and_malformed_entries() {
+        let vars = parse_env_output(&framed(b"_=/usr/bin/env\0SHLVL=2\0PWD=/\0=oops\0noequals\0KEEP=y\0"), MARKER).unwrap();
+        assert_eq!(vars.keys().collect::<Vec<_>>(), ["KEEP"]);
+    }
+
+    #[test]
+    fn rejects_output_without_a_listing() {
+        assert!(parse_env_output(b"zsh: command not found: env", MARKER).is_none());
+        assert!(parse_env_output(MARKER.as_bytes(), MARKER).is_none());
+        assert!(parse_env_output(&framed(b""), MARKER).is_none());
+    }
+
+    #[test]
+    fn shell_kinds_frame_env_with_the_marker() {
+        assert_eq!(ShellKind::of(Path::new("/bin/zsh")), ShellKind::Posix);
+        assert_eq!(ShellKind::of(Path::new("/opt/homebrew/bin/fish")), ShellKind::Fish);
+        assert_eq!(ShellKind::of(Path::new("/usr/bin/nu")), ShellKind::Nu);
+        assert_eq!(ShellKind::of(Path::new("/bin/tcsh")), ShellKind::Other);
+        for kind in [ShellKind::Posix, ShellKind::Fish, ShellKind::Nu, ShellKind::Other] {
+            let script = kind.args(MARKER).pop().unwrap();
+            assert_eq!(script.matches(MARKER).count(), 2, "{kind:?}");
+            assert!(script.contains("env -0"), "{kind:?}");
+        }
+    }
+}
@@ -0,0 +1,502 @@
+//! The startup pipeline and the splash window that reports on it.
+//!
+//! While the login environment is captured, a Node runtime is chosen, a port
+//! is claimed and the server is spawned and probed for readiness, only the
+//! splash is visible; the main window is built hidden once the port (and so
+//! its runtime config) is known. Each phase is emitted on `startup://phase`
+//! for the splash (`splash.html`) to render. Once the server is ready the
+//! main window is shown and the splash is destroyed; if a phase fails, the
+//! splash keeps the error on screen and offers to retry or open the logs.
+//!
+//! The same pipeline backs the `start_server` and `restart_server` commands,
+//! so a restart picks up changed settings, environment and Node runtime.
+//! Relaunches keep the session's port and main window.
+
+use crate::logs::{self, ServerLog, Stream};
+use crate::node::{self, NodeResolver};
+use crate::port;
+use crate::runtime::{self, RuntimeConfig, FEATURES_VAR, TOKEN_VAR};
+use crate::serverenv::ServerEnv;
+use crate::settings::DesktopSettings;
+use crate::shellenv::ShellEnvResolver;
+use crate::supervisor::{ServerLaunch, ServerSlot, Supervisor};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+pub const PHASE_EVENT: &str = "startup://phase";
+pub const SPLASH_WINDOW: &str = "splash";
+pub const MAIN_WINDOW: &str = "main";
+/// Stderr lines quoted when the server never became ready.
+const STDERR_LINES: usize = 20;
+/// Tells the server the shell restarts it, so it must not respawn itself.
+const SUPERVISED_VAR: &str = "STALLION_SUPERVISED";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Phase {
+    ShellEnv,
+    Node,
+    Port,
+    Spawn,
+    Ready,
+}
+
+impl Phase {
+    const ALL: [Phase; 5] = [
+        Phase::ShellEnv,
+        Phase::Node,
+        Phase::Port,
+        Phase::Spawn,
+        Phase::Ready,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PhaseState {
+    Pending,
+    Active,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseStatus {
+    pub phase: Phase,
+    pub state: PhaseState,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupStatus {
+    pub phases: Vec<PhaseStatus>,
+    pub running: bool,
+    pub handed_off: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServerRunState {
+    Starting,
+    Running,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStatus {
+    pub state: ServerRunState,
+    pub pid: Option<u32>,
+    pub port: Option<u16>,
+    pub uptime_ms: Option<u64>,
+    pub restarts: u32,
+    pub runtime_path: Option<PathBuf>,
+    pub runtime_version: Option<String>,
+}
+
+pub struct Startup {
+    app: AppHandle,
+    resource_path: PathBuf,
+    data_dir: PathBuf,
+    home: String,
+    log: Arc<ServerLog>,
+    env_resolver: Arc<ShellEnvResolver>,
+    auth_token: String,
+    /// The port claimed by the first launch. Relaunches keep it, since the
+    /// main window's runtime config points there.
+    port: Mutex<Option<u16>>,
+    /// Crash restarts of replaced supervisors plus manual restarts.
+    past_restarts: AtomicU32,
+    phases: Mutex<Vec<PhaseStatus>>,
+    running: AtomicBool,
+    handed_off: AtomicBool,
+}
+
+impl Startup {
+    pub fn new(
+        app: AppHandle,
+        resource_path: PathBuf,
+        data_dir: PathBuf,
+        home: String,
+        log: Arc<ServerLog>,
+        env_resolver: Arc<ShellEnvResolver>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            app,
+            resource_path,
+            data_dir,
+            home,
+            log,
+            env_resolver,
+            auth_token: runtime::generate_token(),
+            port: Mutex::new(None),
+            past_restarts: AtomicU32::new(0),
+            phases: Mutex::new(pending()),
+            running: AtomicBool::new(false),
+            handed_off: AtomicBool::new(false),
+        })
+    }
+
+    pub fn handed_off(&self) -> bool {
+        self.handed_off.load(Ordering::SeqCst)
+    }
+
+    pub fn status(&self) -> StartupStatus {
+        StartupStatus {
+            phases: self.phases.lock().unwrap().clone(),
+            running: self.running.load(Ordering::SeqCst),
+            handed_off: self.handed_off(),
+        }
+    }
+
+    /// Runs the pipeline on a background thread: a port conflict may need to
+    /// ask the user, and readiness can take a while. Does nothing if a run is
+    /// already in progress; failures are reported through the phases.
+    pub fn run(self: &Arc<Self>) {
+        let this = self.clone();
+        std::thread::spawn(move || {
+            let _ = this.relaunch();
+        });
+    }
+
+    /// Runs the pipeline on the calling thread, first stopping any server
+    /// left from an earlier run, and returns the error of the phase that
+    /// failed.
+    pub fn relaunch(&self) -> Result<(), String> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err("The server is already starting".into());
+        }
+        *self.phases.lock().unwrap() = pending();
+        self.emit();
+        self.stop();
+        let ready = self.launch();
+        self.running.store(false, Ordering::SeqCst);
+        self.emit();
+        if !ready {
+            return Err(self.failure().unwrap_or_else(|| "Server failed to start".into()));
+        }
+        if !self.handed_off() {
+            self.hand_off();
+        }
+        Ok(())
+    }
+
+    /// Relaunches the server on request, counting it as a restart.
+    pub fn restart(&self) -> Result<(), String> {
+        self.past_restarts.fetch_add(1, Ordering::SeqCst);
+        self.relaunch()
+    }
+
+    /// Stops the current server, if any. It stays in the slot so its status
+    /// can still be reported.
+    pub fn stop(&self) {
+        if let Some(current) = self.app.state::<ServerSlot>().current() {
+            current.shutdown();
+        }
+    }
+
+    pub fn server_status(&self) -> ServerStatus {
+        let current = self.app.state::<ServerSlot>().current();
+        let past = self.past_restarts.load(Ordering::SeqCst);
+        let state = if self.running.load(Ordering::SeqCst) {
+            ServerRunState::Starting
+        } else if current.as_ref().is_some_and(|s| s.is_running()) {
+            ServerRunState::Running
+        } else {
+            ServerRunState::Stopped
+        };
+        let Some(current) = current else {
+            return ServerStatus {
+                state,
+                pid: None,
+                port: *self.port.lock().unwrap(),
+                uptime_ms: None,
+                restarts: past,
+                runtime_path: None,
+                runtime_version: None,
+            };
+        };
+        ServerStatus {
+            state,
+            pid: current.pid(),
+            port: Some(current.port()),
+            uptime_ms: current.uptime().map(|d| d.as_millis() as u64),
+            restarts: past + current.restarts(),
+            runtime_path: Some(current.runtime().candidate.path.clone()),
+            runtime_version: current.runtime().candidate.version.map(|v| v.to_string()),
+        }
+    }
+
+    fn failure(&self) -> Option<String> {
+        let phases = self.phases.lock().unwrap();
+        phases
+            .iter()
+            .find(|p| p.state == PhaseState::Failed)
+            .and_then(|p| p.detail.clone())
+    }
+
+    /// Returns whether the server became ready.
+    fn launch(&self) -> bool {
+        let settings = DesktopSettings::load(&self.data_dir);
+        let server_path = self.resource_path.join("dist-server").join("index.js");
+
+        self.begin(Phase::ShellEnv);
+        let shell_env = self.env_resolver.resolve();
+        self.note(&format!(
+            "Login environment from {} ({:?}, {} variables)",
+            shell_env.shell.display(),
+            shell_env.source,
+            shell_env.vars.len()
+        ));
+        self.finish(Phase::ShellEnv, format!("{:?} from {}", shell_env.source, shell_env.shell.display()));
+
+        self.begin(Phase::Node);
+        let runtime = match NodeResolver::new(Path::new(&self.home), &settings.runtime, &shell_env.vars)
+            .resolve()
+        {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                if self.app.get_webview_window(SPLASH_WINDOW).is_none() {
+                    node::report_unusable(&self.app, &e);
+                }
+                return self.fail(Phase::Node, format!("{e}\n\n{}", e.advice()));
+            }
+        };
+        let version = runtime
+            .candidate
+            .version
+            .map_or_else(|| "?".into(), |v| v.to_string());
+        self.note(&format!(
+            "Using Node {version} at {} ({})",
+            runtime.candidate.path.display(),
+            runtime.reason
+        ));
+        self.finish(Phase::Node, format!("{version} at {}", runtime.candidate.path.display()));
+
+        self.begin(Phase::Port);
+        let claimed = *self.port.lock().unwrap();
+        let port = match claimed {
+            Some(port) if port::block_is_free(port) => port,
+            Some(port) => {
+                return self.fail(
+                    Phase::Port,
+                    format!("Port {port} is no longer free; quit and reopen Stallion to pick another"),
+                )
+            }
+            None => match port::claim_port(&self.app, port::PREFERRED_PORT, &server_path, &self.data_dir) {
+                Ok(port) => port,
+                Err(e) => return self.fail(Phase::Port, e),
+            },
+        };
+        *self.port.lock().unwrap() = Some(port);
+        self.finish(Phase::Port, port.to_string());
+
+        self.begin(Phase::Spawn);
+        let server_env = ServerEnv::assemble(&self.data_dir, &self.home, &shell_env.vars, &settings.env);
+        for warning in &server_env.warnings {
+            self.note(warning);
+        }
+        let config = RuntimeConfig::new(
+            &self.app,
+            port,
+            &self.data_dir,
+            server_env.get(FEATURES_VAR),
+            &self.auth_token,
+        );
+        // Once shown, the main window keeps its config: the port and token
+        // never change within a session.
+        if !self.handed_off() {
+            if let Err(e) = self.open_main_window(&config) {
+                return self.fail(Phase::Spawn, e);
+            }
+        }
+
+        let mut env = server_env.pairs();
+        env.push((TOKEN_VAR.into(), self.auth_token.clone()));
+        env.push((SUPERVISED_VAR.into(), "1".into()));
+        let launch = ServerLaunch {
+            runtime,
+            script: server_path,
+            cwd: self.resource_path.clone(),
+            port,
+            env,
+        };
+        let supervisor = Supervisor::new(self.app.clone(), launch, settings.server, self.log.clone());
+        if let Some(previous) = self.app.state::<ServerSlot>().replace(Some(supervisor.clone())) {
+            self.past_restarts.fetch_add(previous.restarts(), Ordering::SeqCst);
+        }
+
+        if let Err(e) = supervisor.start() {
+            return self.fail(Phase::Spawn, format!("Failed to spawn server: {e}"));
+        }
+        self.finish(
+            Phase::Spawn,
+            supervisor.pid().map_or_else(String::new, |pid| format!("pid {pid}")),
+        );
+
+        // Finer-grained readiness phases are reported on `server://state`.
+        self.begin(Phase::Ready);
+        match supervisor.wait_ready() {
+            Ok(()) => {
+                self.finish(Phase::Ready, format!("listening on port {port}"));
+                true
+            }
+            Err(e) => {
+                let tail = supervisor.stderr_tail();
+                let quoted = &tail[tail.len().saturating_sub(STDERR_LINES)..];
+                if quoted.is_empty() {
+                    self.fail(Phase::Ready, e)
+                } else {
+                    self.fail(Phase::Ready, format!("{e}\n\nLast error output:\n{}", quoted.join("\n")))
+                }
+            }
+        }
+    }
+
+    /// Builds the (hidden) main window so the frontend loads while the
+    /// server boots. A window from an earlier attempt may carry a stale port,
+    /// so it is replaced.
+    fn open_main_window(&self, config: &RuntimeConfig) -> Result<(), String> {
+        if let Some(window) = self.app.get_webview_window(MAIN_WINDOW) {
+            let _ = window.destroy();
+        }
+        config
+            .app_window(&self.app, MAIN_WINDOW, "index.html")
+            .title("Stallion")
+            .inner_size(1200.0, 800.0)
+            .resizable(true)
+            .visible(false)
+            .build()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to create window: {e}"))
+    }
+
+    /// Shows the main window and closes the splash.
+    fn hand_off(&self) {
+        self.handed_off.store(true, Ordering::SeqCst);
+        if let Some(main) = self.app.get_webview_window(MAIN_WINDOW) {
+            let _ = main.show();
+            let _ = main.set_focus();
+        }
+        if let Some(splash) = self.app.get_webview_window(SPLASH_WINDOW) {
+            let _ = splash.destroy();
+        }
+    }
+
+    fn begin(&self, phase: Phase) {
+        self.set(phase, PhaseState::Active, None);
+    }
+
+    fn finish(&self, phase: Phase, detail: String) {
+        let detail = (!detail.is_empty()).then_some(detail);
+        self.set(phase, PhaseState::Done, detail);
+    }
+
+    /// Marks `phase` failed and logs why. Returns `false` so callers can
+    /// bail out of the pipeline with it.
+    fn fail(&self, phase: Phase, error: String) -> bool {
+        eprintln!("{error}");
+        for line in error.lines().filter(|l| !l.trim().is_empty()) {
+            self.log.write_line(Stream::Shell, line);
+        }
+        self.set(phase, PhaseState::Failed, Some(error));
+        false
+    }
+
+    fn set(&self, phase: Phase, state: PhaseState, detail: Option<String>) {
+        {
+            let mut phases = self.phases.lock().unwrap();
+            if let Some(status) = phases.iter_mut().find(|s| s.phase == phase) {
+                status.state = state;
+                status.detail = detail;
+            }
+        }
+        self.emit();
+    }
+
+    fn note(&self, line: &str) {
+        eprintln!("{line}");
+        self.log.write_line(Stream::Shell, line);
+    }
+
+    fn emit(&self) {
+        let _ = self.app.emit(PHASE_EVENT, self.status());
+    }
+}
+
+fn pending() -> Vec<PhaseStatus> {
+    Phase::ALL
+        .into_iter()
+        .map(|phase| PhaseStatus {
+            phase,
+            state: PhaseState::Pending,
+            detail: None,
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub fn startup_status(startup: State<'_, Arc<Startup>>) -> StartupStatus {
+    startup.status()
+}
+
+/// Starts the pipeline again, e.g. after installing Node or freeing a port.
+#[tauri::command]
+pub fn retry_startup(startup: State<'_, Arc<Startup>>) {
+    startup.run();
+}
+
+/// Opens the server log directory in the system file manager.
+#[tauri::command]
+pub fn open_logs(app: AppHandle, startup: State<'_, Arc<Startup>>) -> Result<(), String> {
+    logs::open_dir(&app, &startup.data_dir)
+}
+
+#[tauri::command]
+pub fn server_status(startup: State<'_, Arc<Startup>>) -> ServerStatus {
+    startup.server_status()
+}
+
+/// Starts the server if it isn't running. Settings, the login environment
+/// and the Node runtime are re-resolved; the port stays the same.
+#[tauri::command]
+pub async fn start_server(startup: State<'_, Arc<Startup>>) -> Result<ServerStatus, String> {
+    let startup = startup.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        if !matches!(startup.server_status().state, ServerRunState::Running) {
+            startup.relaunch()?;
+        }
+        Ok(startup.server_status())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Stops the server (if running) and starts it again with fresh settings.
+#[tauri::command]
+pub async fn restart_server(startup: State<'_, Arc<Startup>>) -> Result<ServerStatus, String> {
+    let startup = startup.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        startup.restart()?;
+        Ok(startup.server_status())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Stops the server until `start_server` or `restart_server` is called.
+#[tauri::command]
+pub async fn stop_server(startup: State<'_, Arc<Startup>>) -> Result<ServerStatus, String> {
+    let startup = startup.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        startup.stop();
+        startup.server_status()
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
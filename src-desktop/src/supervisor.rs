@@ -0,0 +1,521 @@
+//! Keeps the Node server alive for the lifetime of the app.
+//!
+//! The supervisor owns the server `Child`, polls its exit status from a
+//! watcher thread and restarts it with exponential backoff when it exits
+//! without being asked to. Every transition is emitted on `server://state`
+//! so the frontend can show "reconnecting" instead of a dead UI.
+
+use crate::crash::{self, CrashReport};
+use crate::logs::{ServerLog, Stream};
+use crate::node::Resolution;
+use crate::procgroup;
+use crate::readiness::{self, Probe, ReadyPhase};
+use crate::settings::ServerSettings;
+use serde::Serialize;
+use chrono::{SecondsFormat, Utc};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+pub const STATE_EVENT: &str = "server://state";
+
+/// Uptime after which a crash no longer counts against the restart budget.
+const STABLE_UPTIME: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Unexpected exits remembered for crash reports and diagnostics.
+const EXIT_HISTORY: usize = 20;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ServerState {
+    Starting { attempt: u32 },
+    Spawned { pid: u32 },
+    Booting { phase: ReadyPhase, elapsed_ms: u64 },
+    Ready { port: u16 },
+    NotReady { reason: String },
+    Crashed { code: Option<i32>, restarts: u32 },
+    Restarting { attempt: u32, delay_ms: u64 },
+    Failed { restarts: u32, reason: String },
+    Stopping,
+    Stopped,
+}
+
+/// Everything needed to (re)spawn the server process.
+#[derive(Debug, Clone)]
+pub struct ServerLaunch {
+    pub runtime: Resolution,
+    pub script: PathBuf,
+    pub cwd: PathBuf,
+    pub port: u16,
+    pub env: Vec<(String, String)>,
+}
+
+impl ServerLaunch {
+    /// Both streams are piped: the supervisor logs them and watches stdout
+    /// for the startup banner. The server gets exactly `env`, never the
+    /// app's own environment, so the deny rules hold for variables the app
+    /// itself inherited.
+    fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.runtime.candidate.path);
+        cmd.arg(&self.script)
+            .current_dir(&self.cwd)
+            .env_clear()
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .env("PORT", self.port.to_string())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        procgroup::isolate(&mut cmd);
+        cmd
+    }
+}
+
+/// An exit the supervisor didn't ask for.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExitRecord {
+    pub at: String,
+    pub code: Option<i32>,
+    /// The signal that killed the server, on Unix.
+    pub signal: Option<i32>,
+    pub uptime_ms: u64,
+}
+
+impl ExitRecord {
+    fn new(status: ExitStatus, uptime: Duration) -> Self {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+        #[cfg(not(unix))]
+        let signal = None;
+        Self {
+            at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            code: status.code(),
+            signal,
+            uptime_ms: uptime.as_millis() as u64,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.code, self.signal) {
+            (Some(code), _) => format!("exited with code {code}"),
+            (None, Some(signal)) => format!("was killed by signal {signal}"),
+            (None, None) => "exited".into(),
+        }
+    }
+}
+
+/// The supervisor for the current server launch, if any. Relaunching with
+/// fresh settings replaces it rather than mutating it.
+#[derive(Default)]
+pub struct ServerSlot(Mutex<Option<Arc<Supervisor>>>);
+
+impl ServerSlot {
+    pub fn current(&self) -> Option<Arc<Supervisor>> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Installs `next`, returning the previous supervisor so the caller can
+    /// shut it down outside the lock.
+    pub fn replace(&self, next: Option<Arc<Supervisor>>) -> Option<Arc<Supervisor>> {
+        std::mem::replace(&mut *self.0.lock().unwrap(), next)
+    }
+}
+
+pub struct Supervisor {
+    app: AppHandle,
+    launch: ServerLaunch,
+    settings: ServerSettings,
+    log: Arc<ServerLog>,
+    child: Mutex<Option<Child>>,
+    stopping: AtomicBool,
+    restarts: AtomicU32,
+    /// When the current child was spawned.
+    spawned_at: Mutex<Option<Instant>>,
+    startup_line_seen: Arc<AtomicBool>,
+    /// The current child's last `crash_report_lines` lines of stderr.
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
+    exits: Mutex<VecDeque<ExitRecord>>,
+}
+
+impl Supervisor {
+    pub fn new(
+        app: AppHandle,
+        launch: ServerLaunch,
+        settings: ServerSettings,
+        log: Arc<ServerLog>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            app,
+            launch,
+            settings,
+            log,
+            child: Mutex::new(None),
+            stopping: AtomicBool::new(false),
+            restarts: AtomicU32::new(0),
+            spawned_at: Mutex::new(None),
+            startup_line_seen: Arc::new(AtomicBool::new(false)),
+            stderr_tail: Arc::new(Mutex::new(VecDeque::new())),
+            exits: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.launch.port
+    }
+
+    pub fn pid(&self) -> Option<u32> {
+        self.child.lock().unwrap().as_ref().map(Child::id)
+    }
+
+    pub fn runtime(&self) -> &Resolution {
+        &self.launch.runtime
+    }
+
+    /// How many times the watcher has restarted the server after a crash.
+    pub fn restarts(&self) -> u32 {
+        self.restarts.load(Ordering::SeqCst)
+    }
+
+    pub fn stderr_tail(&self) -> Vec<String> {
+        self.stderr_tail.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Unexpected exits, oldest first.
+    pub fn exits(&self) -> Vec<ExitRecord> {
+        self.exits.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.stopping.load(Ordering::SeqCst) && !self.has_exited()
+    }
+
+    /// Time since the current server process was spawned, if it is running.
+    pub fn uptime(&self) -> Option<Duration> {
+        if !self.is_running() {
+            return None;
+        }
+        self.spawned_at.lock().unwrap().map(|t| t.elapsed())
+    }
+
+    /// Spawns the server and the watcher thread that keeps it running.
+    pub fn start(self: &Arc<Self>) -> std::io::Result<()> {
+        self.spawn(0)?;
+        let this = self.clone();
+        std::thread::spawn(move || this.watch());
+        Ok(())
+    }
+
+    /// Stops the server for good; the watcher will not restart it. The
+    /// server gets SIGTERM and the drain timeout to shut down cleanly, then
+    /// anything left in its process group is killed.
+    pub fn shutdown(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        let child = self.child.lock().unwrap().take();
+        if let Some(mut child) = child {
+            self.emit(ServerState::Stopping);
+            terminate(&mut child, Duration::from_millis(self.settings.drain_timeout_ms));
+            self.log.write_line(Stream::Shell, "Server stopped");
+            self.emit(ServerState::Stopped);
+        }
+    }
+
+    fn spawn(&self, attempt: u32) -> std::io::Result<()> {
+        let mut guard = self.child.lock().unwrap();
+        // Checked under the lock so a concurrent shutdown can't miss this child.
+        if self.stopping.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.emit(ServerState::Starting { attempt });
+        let mut child = self.launch.command().spawn().inspect_err(|e| {
+            self.log.write_line(Stream::Shell, &format!("Failed to spawn server: {e}"));
+        })?;
+        self.log.write_line(
+            Stream::Shell,
+            &format!(
+                "Started server pid {} on port {} (attempt {attempt})",
+                child.id(),
+                self.launch.port
+            ),
+        );
+        self.startup_line_seen.store(false, Ordering::SeqCst);
+        if let Some(stdout) = child.stdout.take() {
+            let log = self.log.clone();
+            let seen = self.startup_line_seen.clone();
+            std::thread::spawn(move || {
+                forward_lines(stdout, |line| {
+                    if line.contains(readiness::STARTUP_MARKER) {
+                        seen.store(true, Ordering::SeqCst);
+                    }
+                    log.write_line(Stream::Stdout, line);
+                })
+            });
+        }
+        self.stderr_tail.lock().unwrap().clear();
+        if let Some(stderr) = child.stderr.take() {
+            let log = self.log.clone();
+            let tail = self.stderr_tail.clone();
+            let keep = self.settings.crash_report_lines;
+            std::thread::spawn(move || {
+                forward_lines(stderr, |line| {
+                    {
+                        let mut tail = tail.lock().unwrap();
+                        if tail.len() >= keep {
+                            tail.pop_front();
+                        }
+                        if keep > 0 {
+                            tail.push_back(line.trim_end_matches(['\r', '\n']).to_string());
+                        }
+                    }
+                    log.write_line(Stream::Stderr, line);
+                })
+            });
+        }
+        self.emit(ServerState::Spawned { pid: child.id() });
+        *self.spawned_at.lock().unwrap() = Some(Instant::now());
+        *guard = Some(child);
+        Ok(())
+    }
+
+    /// Blocks until the server answers its status endpoint (and, if
+    /// configured, has printed its startup banner), reporting each phase.
+    pub fn wait_ready(&self) -> Result<(), String> {
+        let timeout = Duration::from_millis(self.settings.ready_timeout_ms);
+        let start = Instant::now();
+        let mut last_phase = None;
+        while start.elapsed() < timeout {
+            if self.stopping.load(Ordering::SeqCst) {
+                return Err("Server is shutting down".into());
+            }
+            if self.has_exited() {
+                let reason = "Server exited before it became ready".to_string();
+                self.emit(ServerState::NotReady { reason: reason.clone() });
+                return Err(reason);
+            }
+
+            let phase = match readiness::probe_status(self.launch.port) {
+                Probe::Refused => ReadyPhase::Connecting,
+                Probe::NotReady => ReadyPhase::Initializing,
+                Probe::Ready
+                    if self.settings.wait_for_startup_line
+                        && !self.startup_line_seen.load(Ordering::SeqCst) =>
+                {
+                    ReadyPhase::WaitingForStartup
+                }
+                Probe::Ready => {
+                    self.emit(ServerState::Ready { port: self.launch.port });
+                    return Ok(());
+                }
+            };
+            if last_phase != Some(phase) {
+                last_phase = Some(phase);
+                self.emit(ServerState::Booting {
+                    phase,
+                    elapsed_ms: start.elapsed().as_millis() as u64,
+                });
+            }
+            std::thread::sleep(READY_POLL_INTERVAL);
+        }
+
+        let reason = format!(
+            "Server did not become ready within {}s",
+            timeout.as_secs()
+        );
+        self.emit(ServerState::NotReady { reason: reason.clone() });
+        Err(reason)
+    }
+
+    /// Checks for exit without reaping the child out of the supervisor, so
+    /// the watcher still sees and handles the crash.
+    fn has_exited(&self) -> bool {
+        match self.child.lock().unwrap().as_mut() {
+            Some(child) => !matches!(child.try_wait(), Ok(None)),
+            None => true,
+        }
+    }
+
+    fn watch(&self) {
+        let mut started = Instant::now();
+        let mut crashes = 0u32;
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            if self.stopping.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let status = {
+                let mut guard = self.child.lock().unwrap();
+                match guard.as_mut() {
+                    // The last restart failed to spawn; treat it as another crash.
+                    None => None,
+                    Some(child) => match child.try_wait() {
+                        Ok(Some(status)) => {
+                            // Don't let the crashed server's children outlive it.
+                            procgroup::kill(child.id());
+                            guard.take();
+                            Some(status)
+                        }
+                        Ok(None) => continue,
+                        Err(e) => {
+                            eprintln!("Failed to poll server process: {e}");
+                            continue;
+                        }
+                    },
+                }
+            };
+            if self.stopping.load(Ordering::SeqCst) {
+                return;
+            }
+
+            if started.elapsed() >= STABLE_UPTIME {
+                crashes = 0;
+            }
+            crashes += 1;
+            let restarts = self.restarts.load(Ordering::SeqCst);
+            let code = status.and_then(|s| s.code());
+            if let Some(status) = status {
+                let uptime = self.spawned_at.lock().unwrap().map_or(Duration::ZERO, |t| t.elapsed());
+                let mut exits = self.exits.lock().unwrap();
+                if exits.len() >= EXIT_HISTORY {
+                    exits.pop_front();
+                }
+                exits.push_back(ExitRecord::new(status, uptime));
+            }
+            eprintln!("Server exited unexpectedly (code {code:?})");
+            self.log.write_line(
+                Stream::Shell,
+                &format!("Server exited unexpectedly (code {code:?})"),
+            );
+            self.emit(ServerState::Crashed { code, restarts });
+
+            if crashes > self.settings.max_restarts {
+                let reason = format!("Server crashed {crashes} times in a row");
+                self.emit(ServerState::Failed {
+                    restarts,
+                    reason: reason.clone(),
+                });
+                crash::report(&self.app, CrashReport::new(self, reason));
+                return;
+            }
+
+            let delay = backoff(&self.settings, crashes);
+            self.emit(ServerState::Restarting {
+                attempt: crashes,
+                delay_ms: delay.as_millis() as u64,
+            });
+            if !self.sleep_unless_stopping(delay) {
+                return;
+            }
+
+            self.restarts.fetch_add(1, Ordering::SeqCst);
+            started = Instant::now();
+            match self.spawn(crashes) {
+                Ok(()) => {
+                    if let Err(e) = self.wait_ready() {
+                        eprintln!("{e}");
+                    }
+                }
+                Err(e) => eprintln!("Failed to restart server: {e}"),
+            }
+        }
+    }
+
+    /// Returns `false` if shutdown was requested while sleeping.
+    fn sleep_unless_stopping(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        while Instant::now() < deadline {
+            if self.stopping.load(Ordering::SeqCst) {
+                return false;
+            }
+            std::thread::sleep(POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+        }
+        !self.stopping.load(Ordering::SeqCst)
+    }
+
+    fn emit(&self, state: ServerState) {
+        let _ = self.app.emit(STATE_EVENT, state);
+    }
+}
+
+/// Sends SIGTERM to the server, waits up to `drain` for it to exit, then
+/// SIGKILLs its process group to catch stragglers and orphans.
+#[cfg(unix)]
+fn terminate(child: &mut Child, drain: Duration) {
+    // SAFETY: plain syscall on a pid we spawned and have not yet reaped.
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+    }
+    let deadline = Instant::now() + drain;
+    while Instant::now() < deadline {
+        if !matches!(child.try_wait(), Ok(None)) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    procgroup::kill_and_reap(child);
+}
+
+#[cfg(not(unix))]
+fn terminate(child: &mut Child, _drain: Duration) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Feeds each line of a child stream to `on_line` until EOF. Reads raw
+/// lines so non-UTF-8 output can't stop the pipe from draining.
+fn forward_lines(stream: impl Read, mut on_line: impl FnMut(&str)) {
+    let mut reader = BufReader::new(stream);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => on_line(&String::from_utf8_lossy(&buf)),
+        }
+    }
+}
+
+fn backoff(settings: &ServerSettings, crashes: u32) -> Duration {
+    let factor = 1u64 << crashes.saturating_sub(1).min(16);
+    Duration::from_millis(
+        settings
+            .restart_backoff_ms
+            .saturating_mul(factor)
+            .min(settings.max_backoff_ms),
+    )
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::node::{Candidate, Source};
+
+    #[test]
+    fn server_gets_only_the_assembled_env() {
+        std::env::set_var("STALLION_TEST_APP_ONLY", "leaked");
+        // `env -0` stands in for `node <script>`.
+        let launch = ServerLaunch {
+            runtime: Resolution {
+                candidate: Candidate { path: "/usr/bin/env".into(), source: Source::System, version: None },
+                reason: String::new(),
+            },
+            script: "-0".into(),
+            cwd: std::env::temp_dir(),
+            port: 4321,
+            env: vec![("KEEP".into(), "1".into())],
+        };
+        let output = launch.command().output().unwrap();
+        let mut vars: Vec<String> = output
+            .stdout
+            .split(|b| *b == 0)
+            .filter(|v| !v.is_empty())
+            .map(|v| String::from_utf8_lossy(v).into_owned())
+            .collect();
+        vars.sort();
+        assert_eq!(vars, ["KEEP=1", "PORT=4321"]);
+    }
+}
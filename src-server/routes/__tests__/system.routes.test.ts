@@ -80,4 +80,27 @@ describe('System Routes', () => {
     const body = await json(await app.request('/terminal-port'));
     expect(body.port).toBe(3142);
   });
+
+  test('GET /activity reports idle without an activity source', async () => {
+    const app = createSystemRoutes(createMockDeps() as any, mockLogger);
+    const body = await json(await app.request('/activity'));
+    expect(body).toEqual({ streamingAgents: [], runningJobs: [], busy: false });
+  });
+
+  test('GET /activity reports streaming agents and running jobs', async () => {
+    const app = createSystemRoutes(
+      {
+        ...createMockDeps(),
+        getActivity: () => ({
+          streamingAgents: ['default'],
+          runningJobs: ['daily-digest'],
+        }),
+      } as any,
+      mockLogger,
+    );
+    const body = await json(await app.request('/activity'));
+    expect(body.streamingAgents).toEqual(['default']);
+    expect(body.runningJobs).toEqual(['daily-digest']);
+    expect(body.busy).toBe(true);
+  });
 });
@@ -31,6 +31,7 @@ interface SystemStatusDeps {
   eventBus?: { emit: (event: string, data?: Record<string, unknown>) => void };
   appConfig?: { runtime?: string };
   port?: number;
+  getActivity?: () => { streamingAgents: string[]; runningJobs: string[] };
 }
 
 export function createSystemRoutes(deps: SystemStatusDeps, logger: any) {
@@ -229,6 +230,18 @@ export function createSystemRoutes(deps: SystemStatusDeps, logger: any) {
 
       deps.eventBus?.emit('core:updated', { hash: newHash });
 
+      // The desktop shell restarts a supervised server itself; respawning
+      // here would leave an orphan the shell doesn't know about.
+      if (process.env.STALLION_SUPERVISED) {
+        return c.json({
+          success: true,
+          hash: newHash,
+          message: `Updated to ${newHash}. Restart the server to apply.`,
+          restarting: false,
+          restartRequired: true,
+        });
+      }
+
       // Schedule graceful self-restart after response is sent
       const port = process.env.PORT || '3141';
       const pidFile = join(gitRoot, '.stallion.pids');
@@ -352,5 +365,18 @@ export function createSystemRoutes(deps: SystemStatusDeps, logger: any) {
     return c.json({ success: true, port: port + 1 });
   });
 
+  // In-flight work — the desktop shell asks before quitting
+  app.get('/activity', (c) => {
+    const activity = deps.getActivity?.() ?? {
+      streamingAgents: [],
+      runningJobs: [],
+    };
+    return c.json({
+      ...activity,
+      busy:
+        activity.streamingAgents.length > 0 || activity.runningJobs.length > 0,
+    });
+  });
+
   return app;
 }
@@ -921,6 +921,12 @@ export class StallionRuntime {
           eventBus: this.eventBus,
           appConfig: this.appConfig,
           port: this.port,
+          getActivity: () => ({
+            streamingAgents: [...this.agentStatus]
+              .filter(([, status]) => status === 'running')
+              .map(([slug]) => slug),
+            runningJobs: this.schedulerService?.getRunningJobs() ?? [],
+          }),
         },
         this.logger,
       ),
@@ -21,6 +21,7 @@ describe('permission tiers', () => {
   test('active permissions', () => {
     expect(getPermissionTier('network.fetch')).toBe('active');
     expect(getPermissionTier('storage.write')).toBe('active');
+    expect(getPermissionTier('secrets.store')).toBe('active');
   });
 
   test('trusted permissions', () => {
@@ -101,6 +101,11 @@ export class BuiltinScheduler implements ISchedulerProvider {
     await Promise.all(this.runningJobs.values());
   }
 
+  /** Names of jobs currently executing */
+  getRunningJobs(): string[] {
+    return [...this.running];
+  }
+
   private trackJob(name: string): () => void {
     let resolve: () => void;
     const p = new Promise<void>((r) => {
@@ -19,6 +19,7 @@ const TIER_MAP: Record<string, PermissionTier> = {
   'storage.write': 'active',
   'agents.invoke': 'active',
   'tools.invoke': 'active',
+  'secrets.store': 'active',
   'providers.register': 'trusted',
   'system.config': 'trusted',
 };
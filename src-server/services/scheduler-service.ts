@@ -49,6 +49,11 @@ export class SchedulerService {
     this.builtin.setNotificationService(ns);
   }
 
+  /** Built-in jobs currently executing */
+  getRunningJobs(): string[] {
+    return this.builtin.getRunningJobs();
+  }
+
   /** Register an additional scheduler provider (from plugin) */
   addProvider(provider: ISchedulerProvider) {
     this.providers.set(provider.id, provider);
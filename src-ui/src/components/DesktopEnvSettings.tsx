@@ -0,0 +1,196 @@
+import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query';
+import { useEffect, useState } from 'react';
+import {
+  type EnvProfile,
+  getEnvProfile,
+  getServerEnv,
+  saveEnvProfile,
+} from '../lib/tauri';
+import './DesktopEnvSettings.css';
+
+const ORIGIN_LABEL = {
+  login: 'login shell',
+  profile: 'desktop.env',
+  shell: 'app',
+} as const;
+
+const splitPatterns = (text: string) =>
+  text
+    .split(/[\s,]+/)
+    .map((p) => p.trim())
+    .filter(Boolean);
+
+/**
+ * Desktop-only editor for the server's environment: `desktop.env` plus the
+ * allow/deny rules for login shell variables, and a redacted view of the
+ * resulting environment.
+ */
+export function DesktopEnvSettings() {
+  const queryClient = useQueryClient();
+  const [contents, setContents] = useState('');
+  const [allow, setAllow] = useState('');
+  const [deny, setDeny] = useState('');
+  const [showEffective, setShowEffective] = useState(false);
+  const [filter, setFilter] = useState('');
+
+  const { data: profile } = useQuery<EnvProfile>({
+    queryKey: ['desktop-env-profile'],
+    queryFn: getEnvProfile,
+  });
+
+  useEffect(() => {
+    if (!profile) return;
+    setContents(profile.contents);
+    setAllow(profile.allow.join(', '));
+    setDeny(profile.deny.join(', '));
+  }, [profile]);
+
+  const { data: effective, isFetching: loadingEffective } = useQuery({
+    queryKey: ['desktop-env-effective'],
+    queryFn: getServerEnv,
+    enabled: showEffective,
+  });
+
+  const save = useMutation({
+    mutationFn: () =>
+      saveEnvProfile({
+        path: profile?.path ?? '',
+        contents,
+        allow: splitPatterns(allow),
+        deny: splitPatterns(deny),
+      }),
+    onSuccess: () => {
+      queryClient.invalidateQueries({ queryKey: ['desktop-env-profile'] });
+      queryClient.invalidateQueries({ queryKey: ['desktop-env-effective'] });
+    },
+  });
+
+  const dirty =
+    !!profile &&
+    (contents !== profile.contents ||
+      splitPatterns(allow).join(',') !== profile.allow.join(',') ||
+      splitPatterns(deny).join(',') !== profile.deny.join(','));
+
+  const warnings = save.data ?? effective?.warnings ?? [];
+  const query = filter.trim().toLowerCase();
+  const entries = (effective?.entries ?? []).filter(
+    (e) => !query || e.name.toLowerCase().includes(query),
+  );
+
+  return (
+    <div className="desktop-env">
+      <label className="settings__field-label" htmlFor="desktop-env-file">
+        Variables ({profile?.path ?? '~/.stallion-ai/desktop.env'})
+      </label>
+      <textarea
+        id="desktop-env-file"
+        rows={6}
+        spellCheck={false}
+        value={contents}
+        placeholder={
+          'AWS_REGION=us-west-2\nOTEL_EXPORTER_OTLP_ENDPOINT=http://localhost:4318'
+        }
+        onChange={(e) => setContents(e.target.value)}
+      />
+      <span className="settings__field-hint">
+        dotenv syntax. Use <code>${'{NAME}'}</code> to build on an earlier or
+        inherited value, e.g. <code>PATH=${'{PATH}'}:/opt/tools/bin</code>.
+      </span>
+
+      <div className="desktop-env__rules">
+        <div>
+          <label className="settings__field-label" htmlFor="desktop-env-allow">
+            Pass through from login shell
+          </label>
+          <input
+            id="desktop-env-allow"
+            type="text"
+            value={allow}
+            placeholder="*"
+            onChange={(e) => setAllow(e.target.value)}
+          />
+        </div>
+        <div>
+          <label className="settings__field-label" htmlFor="desktop-env-deny">
+            Never pass through
+          </label>
+          <input
+            id="desktop-env-deny"
+            type="text"
+            value={deny}
+            placeholder="SSH_*, *_TOKEN"
+            onChange={(e) => setDeny(e.target.value)}
+          />
+        </div>
+      </div>
+      <span className="settings__field-hint">
+        Comma-separated names; <code>*</code> matches anything. Deny wins over
+        allow, and variables in desktop.env are always applied. Changes take
+        effect the next time the server starts.
+      </span>
+
+      {warnings.length > 0 && (
+        <ul className="desktop-env__warnings">
+          {warnings.map((w) => (
+            <li key={w}>{w}</li>
+          ))}
+        </ul>
+      )}
+      {save.error && (
+        <div className="settings__update-msg settings__update-msg--error">
+          {String(save.error)}
+        </div>
+      )}
+
+      <div className="settings__export-row desktop-env__actions">
+        <button
+          type="button"
+          className="settings__secondary-btn"
+          onClick={() => save.mutate()}
+          disabled={!dirty || save.isPending}
+        >
+          {save.isPending ? 'Saving…' : 'Save Environment'}
+        </button>
+        <button
+          type="button"
+          className="settings__secondary-btn"
+          onClick={() => setShowEffective((v) => !v)}
+        >
+          {showEffective ? 'Hide' : 'Show'} Effective Environment
+        </button>
+      </div>
+
+      {showEffective && (
+        <div className="desktop-env__effective">
+          <input
+            type="text"
+            value={filter}
+            placeholder="Filter variables…"
+            onChange={(e) => setFilter(e.target.value)}
+          />
+          {loadingEffective && !effective ? (
+            <div className="settings__field-hint">Resolving…</div>
+          ) : (
+            <table className="desktop-env__table">
+              <tbody>
+                {entries.map((e) => (
+                  <tr key={e.name}>
+                    <td className="desktop-env__name">{e.name}</td>
+                    <td
+                      className={`desktop-env__value${e.redacted ? ' desktop-env__value--redacted' : ''}`}
+                    >
+                      {e.value}
+                    </td>
+                    <td className="desktop-env__origin">
+                      {ORIGIN_LABEL[e.origin]}
+                    </td>
+                  </tr>
+                ))}
+              </tbody>
+            </table>
+          )}
+        </div>
+      )}
+    </div>
+  );
+}
@@ -1,28 +1,61 @@
 import { useState } from 'react';
 import { log } from '@/utils/logger';
 
+/** Why `authenticate_external` failed, as reported by the desktop shell. */
+export interface ExternalAuthError {
+  kind:
+    | 'unknownProvider'
+    | 'pinRequired'
+    | 'missingBinary'
+    | 'timeout'
+    | 'cancelled'
+    | 'busy'
+    | 'notRunning'
+    | 'badPin'
+    | 'failed'
+    | 'invalidConfig'
+    | 'io';
+  message: string;
+}
+
+function isAuthError(err: unknown): err is ExternalAuthError {
+  return typeof err === 'object' && err !== null && 'kind' in err;
+}
+
 export function useExternalAuth() {
   const [isAuthenticating, setIsAuthenticating] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  const [errorKind, setErrorKind] = useState<ExternalAuthError['kind'] | null>(
+    null,
+  );
 
-  const authenticate = async (pin: string): Promise<boolean> => {
+  // `pin` is only needed by providers that read one (`needsPin`).
+  const authenticate = async (
+    provider: string,
+    pin?: string,
+  ): Promise<boolean> => {
     setIsAuthenticating(true);
     setError(null);
+    setErrorKind(null);
 
     try {
       const { invoke } = await import('@tauri-apps/api/core');
-      await invoke('authenticate_external', { pin });
+      await invoke('authenticate_external', { provider, pin });
       return true;
     } catch (err: any) {
-      // Tauri not available (browser mode) or auth command not configured
-      if (
+      if (isAuthError(err)) {
+        if (err.kind !== 'cancelled') log.api('[Auth] Failed:', err);
+        setError(err.message);
+        setErrorKind(err.kind);
+      } else if (
+        // Tauri not available (browser mode)
         err?.toString?.().includes('not a function') ||
         err?.toString?.().includes('Could not resolve')
       ) {
         setError('Desktop auth not available in browser mode');
       } else {
         log.api('[Auth] Failed:', err);
-        setError(err as string);
+        setError(String(err));
       }
       return false;
     } finally {
@@ -30,5 +63,14 @@ export function useExternalAuth() {
     }
   };
 
-  return { authenticate, isAuthenticating, error };
+  const cancel = async (provider: string): Promise<void> => {
+    try {
+      const { invoke } = await import('@tauri-apps/api/core');
+      await invoke('cancel_authentication', { provider });
+    } catch (err) {
+      log.api('[Auth] Cancel failed:', err);
+    }
+  };
+
+  return { authenticate, cancel, isAuthenticating, error, errorKind };
 }
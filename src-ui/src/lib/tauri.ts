@@ -2,11 +2,42 @@
  * Tauri integration utilities
  */
 
+import type { UnlistenFn } from '@tauri-apps/api/event';
 import { log } from '@/utils/logger';
 
-// Check if we're running in Tauri
+// Check if we're running in Tauri. `__TAURI__` only exists when
+// `withGlobalTauri` is enabled; `__TAURI_INTERNALS__` always does.
 export function isTauriApp(): boolean {
-  return typeof window !== 'undefined' && '__TAURI__' in window;
+  return (
+    typeof window !== 'undefined' &&
+    ('__TAURI__' in window || '__TAURI_INTERNALS__' in window)
+  );
+}
+
+/** Values the desktop shell defines before any page script runs. */
+export interface StallionRuntime {
+  apiBase: string;
+  port: number;
+  shellVersion: string;
+  platform: string;
+  arch: string;
+  dataDir: string;
+  features: string[];
+  authToken: string;
+}
+
+export function getStallionRuntime(): StallionRuntime | undefined {
+  if (typeof window === 'undefined') return undefined;
+  return (window as Window & { __STALLION_RUNTIME__?: StallionRuntime })
+    .__STALLION_RUNTIME__;
+}
+
+async function invoke<T>(
+  command: string,
+  args?: Record<string, unknown>,
+): Promise<T> {
+  const { invoke } = await import('@tauri-apps/api/core');
+  return invoke<T>(command, args);
 }
 
 // Open a URL in a new Tauri WebView window (bypasses X-Frame-Options)
@@ -29,3 +60,205 @@ export async function openResearchUrl(
     window.open(url, '_blank', 'noopener,noreferrer');
   }
 }
+
+/* ── Server environment (desktop.env + passthrough rules) ── */
+
+export interface EnvProfile {
+  path: string;
+  contents: string;
+  allow: string[];
+  deny: string[];
+}
+
+export interface EnvEntry {
+  name: string;
+  value: string;
+  origin: 'login' | 'profile' | 'shell';
+  redacted: boolean;
+}
+
+export interface EffectiveEnv {
+  entries: EnvEntry[];
+  warnings: string[];
+}
+
+export function getEnvProfile(): Promise<EnvProfile> {
+  return invoke('get_env_profile');
+}
+
+// Resolves with the problems found in desktop.env, if any.
+export function saveEnvProfile(profile: EnvProfile): Promise<string[]> {
+  return invoke('save_env_profile', { profile });
+}
+
+export function getServerEnv(): Promise<EffectiveEnv> {
+  return invoke('get_server_env');
+}
+
+export interface ServerStatus {
+  state: 'starting' | 'running' | 'stopped';
+  pid: number | null;
+  port: number | null;
+  uptimeMs: number | null;
+  restarts: number;
+  runtimePath: string | null;
+  runtimeVersion: string | null;
+}
+
+// Lifecycle of the server the desktop shell spawned. Start and restart
+// re-read settings and the environment, and resolve once the server is ready.
+export function getServerStatus(): Promise<ServerStatus> {
+  return invoke('server_status');
+}
+
+export function startServer(): Promise<ServerStatus> {
+  return invoke('start_server');
+}
+
+export function stopServer(): Promise<ServerStatus> {
+  return invoke('stop_server');
+}
+
+export function restartServer(): Promise<ServerStatus> {
+  return invoke('restart_server');
+}
+
+// Writes a zip of logs, runtime details and redacted config for bug
+// reports (to Downloads unless a destination is given) and resolves with
+// its path.
+export function exportDiagnostics(destination?: string): Promise<string> {
+  return invoke('export_diagnostics', { destination });
+}
+
+/* ── External auth providers (auth.providers in desktop.json) ── */
+
+export interface AuthProviderInfo {
+  id: string;
+  name: string;
+  program: string | null;
+  needsPin: boolean;
+  running: boolean;
+  lastSuccess: string | null;
+}
+
+export function listAuthProviders(): Promise<AuthProviderInfo[]> {
+  return invoke('list_auth_providers');
+}
+
+export interface AuthOutput {
+  provider: string;
+  stream: 'stdout' | 'stderr';
+  line: string;
+  // Written without a newline, usually a prompt waiting for input.
+  partial: boolean;
+}
+
+export interface AuthPrompt {
+  provider: string;
+  url: string | null;
+  code: string | null;
+  // Whether the shell already opened `url` in the browser.
+  opened: boolean;
+}
+
+// Output of running auth commands, line by line as it's printed.
+export async function onAuthOutput(
+  handler: (output: AuthOutput) => void,
+): Promise<UnlistenFn> {
+  const { listen } = await import('@tauri-apps/api/event');
+  return listen<AuthOutput>('auth://output', (e) => handler(e.payload));
+}
+
+// URLs and device codes found in that output.
+export async function onAuthPrompt(
+  handler: (prompt: AuthPrompt) => void,
+): Promise<UnlistenFn> {
+  const { listen } = await import('@tauri-apps/api/event');
+  return listen<AuthPrompt>('auth://prompt', (e) => handler(e.payload));
+}
+
+// Answers a prompt from a running provider marked `interactive`.
+export function sendAuthInput(provider: string, input: string): Promise<void> {
+  return invoke('send_auth_input', { provider, input });
+}
+
+/* ── OAuth (authorization code + PKCE through the system browser) ── */
+
+export interface OAuthRequest {
+  authorizeUrl: string;
+  tokenUrl: string;
+  clientId: string;
+  // Only for providers that want one even with PKCE.
+  clientSecret?: string;
+  scopes?: string[];
+  extraParams?: Record<string, string>;
+  // For providers that register the exact redirect URI.
+  redirectPort?: number;
+  redirectPath?: string;
+  timeoutMs?: number;
+  // Tokens are returned unless sent to a path on the local server.
+  deliver?: { to: 'caller' } | { to: 'server'; path: string };
+}
+
+export interface OAuthError {
+  kind:
+    | 'invalidRequest'
+    | 'busy'
+    | 'listener'
+    | 'browser'
+    | 'timeout'
+    | 'cancelled'
+    | 'denied'
+    | 'stateMismatch'
+    | 'exchange'
+    | 'delivery';
+  message: string;
+}
+
+// Resolves with the token endpoint's response, or null when the tokens went
+// to the server. Rejects with an `OAuthError`.
+export function startOAuthFlow(
+  request: OAuthRequest,
+): Promise<Record<string, unknown> | null> {
+  return invoke('start_oauth_flow', { request });
+}
+
+export function cancelOAuthFlow(): Promise<boolean> {
+  return invoke('cancel_oauth_flow');
+}
+
+/* ── Plugin secrets (keyring, or an encrypted file without one) ── */
+
+export interface SecretError {
+  kind:
+    | 'invalidName'
+    | 'unknownPlugin'
+    | 'notPermitted'
+    | 'tooLarge'
+    | 'keyring'
+    | 'store';
+  message: string;
+}
+
+// Each secret belongs to `plugin`, which needs the `secrets.store`
+// permission. All of these reject with a `SecretError`.
+export function secretSet(
+  plugin: string,
+  key: string,
+  value: string,
+): Promise<void> {
+  return invoke('secret_set', { plugin, key, value });
+}
+
+export function secretGet(plugin: string, key: string): Promise<string | null> {
+  return invoke('secret_get', { plugin, key });
+}
+
+// Resolves false when there was no such secret.
+export function secretDelete(plugin: string, key: string): Promise<boolean> {
+  return invoke('secret_delete', { plugin, key });
+}
+
+export function secretList(plugin: string): Promise<string[]> {
+  return invoke('secret_list', { plugin });
+}
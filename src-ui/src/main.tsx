@@ -51,6 +51,7 @@ import { VoiceProviderContext } from './contexts/VoiceProviderContext';
 import { WorkflowsProvider } from './contexts/WorkflowsContext';
 import { PermissionManager } from './core/PermissionManager';
 import { pluginRegistry } from './core/PluginRegistry';
+import { getStallionRuntime } from './lib/tauri';
 // Register default voice + context providers
 import './providers/voice/index';
 import './providers/context/index';
@@ -77,8 +78,10 @@ window.addEventListener('hashchange', () => {
 });
 
 const API_BASE = (() => {
-  // Prefer injected base from CLI --base flag
-  const injected = (window as Window & { __API_BASE__?: string }).__API_BASE__;
+  // Prefer the base injected by the desktop shell or the CLI --base flag
+  const injected =
+    getStallionRuntime()?.apiBase ??
+    (window as Window & { __API_BASE__?: string }).__API_BASE__;
   if (injected) return injected;
   // Prefer the active connection URL from the connect system (stored in localStorage)
   try {
@@ -5,6 +5,7 @@ import { useInvalidateQuery } from '@stallion-ai/sdk';
 import { useMutation, useQuery } from '@tanstack/react-query';
 import { useEffect, useState } from 'react';
 import { ConfirmModal } from '../components/ConfirmModal';
+import { DesktopEnvSettings } from '../components/DesktopEnvSettings';
 import { ModelSelector } from '../components/ModelSelector';
 import { ThemeToggle } from '../components/ThemeToggle';
 import { Toggle } from '../components/Toggle';
@@ -16,6 +17,11 @@ import { useCloseShortcut } from '../hooks/useCloseShortcut';
 import type { FeatureSettings } from '../hooks/useFeatureSettings';
 import { useFeatureSettings } from '../hooks/useFeatureSettings';
 import { usePushNotifications } from '../hooks/usePushNotifications';
+import {
+  exportDiagnostics,
+  isTauriApp,
+  restartServer,
+} from '../lib/tauri';
 import type { AppConfig, NavigationView } from '../types';
 
 function MobilePairingSection() {
@@ -211,6 +217,36 @@ function EnvironmentStatus({ apiBase }: { apiBase: string }) {
 
 /* ── Core Update Check ── */
 
+function DiagnosticsExport() {
+  const exportBundle = useMutation({ mutationFn: () => exportDiagnostics() });
+
+  return (
+    <div className="settings__field">
+      <label className="settings__field-label">Diagnostics</label>
+      <div className="settings__export-row">
+        <button
+          type="button"
+          className="settings__secondary-btn"
+          onClick={() => exportBundle.mutate()}
+          disabled={exportBundle.isPending}
+        >
+          {exportBundle.isPending ? 'Exporting…' : 'Export Diagnostics'}
+        </button>
+      </div>
+      <span className="settings__field-hint">
+        {exportBundle.data
+          ? `Saved to ${exportBundle.data}`
+          : 'Logs, runtime details and config for bug reports, with secrets redacted.'}
+      </span>
+      {exportBundle.error && (
+        <div className="settings__update-msg settings__update-msg--error">
+          {String(exportBundle.error)}
+        </div>
+      )}
+    </div>
+  );
+}
+
 function CoreUpdateCheck({ apiBase }: { apiBase: string }) {
   const [restarting, setRestarting] = useState(false);
 
@@ -238,8 +274,17 @@ function CoreUpdateCheck({ apiBase }: { apiBase: string }) {
       });
       return res.json();
     },
-    onSuccess: (data) => {
-      if (data.success && data.restarting) {
+    onSuccess: async (data) => {
+      if (data.success && data.restartRequired && isTauriApp()) {
+        // Supervised by the desktop shell, which resolves once ready
+        setRestarting(true);
+        try {
+          await restartServer();
+        } finally {
+          setRestarting(false);
+          check();
+        }
+      } else if (data.success && data.restarting) {
         setRestarting(true);
         const poll = setInterval(async () => {
           try {
@@ -755,7 +800,7 @@ export function SettingsView({
     'section-voice':
       'voice speech text tts stt features geolocation timezone mobile pairing offline queue',
     'section-system':
-      'system update log level export import backup restore reset defaults',
+      'system update log level export import backup restore reset defaults desktop server environment variables env proxy diagnostics bug report',
   };
   const sectionVisible = (id: string) => {
     if (!searchQuery.trim()) return true;
@@ -1246,6 +1291,17 @@ export function SettingsView({
               </span>
             </div>
 
+            {isTauriApp() && (
+              <div className="settings__field">
+                <label className="settings__field-label">
+                  Server Environment
+                </label>
+                <DesktopEnvSettings />
+              </div>
+            )}
+
+            {isTauriApp() && <DiagnosticsExport />}
+
             <div className="settings__field">
               <label className="settings__field-label">Backup & Restore</label>
               <div className="settings__export-row">